@@ -1,16 +1,26 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Default, Debug)]
 pub struct Cache {
     pub last_address: String,
     pub last_username: String,
+
+    #[serde(default)]
+    pub fingerprints: HashMap<String, String>,
 }
 
 impl Cache {
-    pub fn new(last_ip: String, last_username: String) -> Self {
+    pub fn new(
+        last_ip: String,
+        last_username: String,
+        fingerprints: HashMap<String, String>,
+    ) -> Self {
         Self {
             last_address: last_ip,
-            last_username
+            last_username,
+            fingerprints,
         } 
     }
 }
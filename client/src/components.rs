@@ -8,6 +8,7 @@ use iced::{
     },
     Alignment, Length, Renderer,
 };
+use models::{ConsoleLine, Stream};
 
 use crate::{
     theme::{self, Theme},
@@ -67,9 +68,15 @@ where
 {
     pub server_id: String,
     pub status: bool,
-    pub console: Vec<String>,
+    pub restarts: u32,
+    pub exit: Option<String>,
+    pub metrics: Option<String>,
+    pub sparkline: Option<String>,
+    pub console: Vec<ConsoleLine>,
 
     pub toggle: M,
+    pub kill: M,
+    pub edit: M,
     pub send: F,
 }
 
@@ -91,6 +98,8 @@ impl Default for CardState {
 pub enum CardMessage {
     Expand,
     ToggleServer,
+    KillServer,
+    EditServer,
     UpdateCommand(String),
     SubmitCommand,
 }
@@ -113,6 +122,8 @@ where
                 None
             }
             ToggleServer => Some(self.toggle.clone()),
+            KillServer => Some(self.kill.clone()),
+            EditServer => Some(self.edit.clone()),
             UpdateCommand(s) => {
                 state.command = s;
                 None
@@ -147,6 +158,21 @@ where
 
         let id: Element<'_, _> = Text::new(&self.server_id).size(30).into();
 
+        let mut hints = vec![];
+
+        if let Some(exit) = &self.exit {
+            hints.push(exit.clone());
+        }
+
+        if self.restarts > 0 {
+            hints.push(format!("Restarted {} times", self.restarts));
+        }
+
+        let hints: Element<'_, _> = Text::new(hints.join(", "))
+            .size(20)
+            .style(theme::Text::Hint)
+            .into();
+
         let handle = Handle::from_memory(match state.expanded {
             true => EXPAND_ARROW,
             false => EXPAND_ARROW_CLOSED,
@@ -156,7 +182,8 @@ where
             .height(Length::Fill)
             .center_y();
 
-        let status_row = row!(id, Space::new(Length::Fill, Length::Fill), icon,)
+        let status_row = row!(id, Space::new(Length::Fill, Length::Fill), hints, icon,)
+            .spacing(20)
             .align_items(Alignment::Center)
             .padding([0, 20])
             .height(Length::Fill);
@@ -175,14 +202,32 @@ where
 
         let mut col = Column::new().push(status_row);
 
+        if let Some(metrics) = &self.metrics {
+            let mut metrics_row = row!(Text::new(metrics).size(18).style(theme::Text::Hint))
+                .spacing(20)
+                .padding([5, 20]);
+
+            if let Some(sparkline) = &self.sparkline {
+                metrics_row = metrics_row.push(Text::new(sparkline).size(18));
+            }
+
+            col = col.push(metrics_row);
+        }
+
         if state.expanded {
             let content: Vec<Element<'_, Self::Event>> = if self.console.len() == 0 {
                 vec![Text::new("[KitPanel] No logs yet").size(20).into()]
             } else {
                 self.console
                     .iter()
-                    .rev()
-                    .map(|i| Text::new(i).size(20).into())
+                    .map(|i| {
+                        let text = Text::new(&i.content).size(20);
+
+                        match i.stream {
+                            Stream::Stderr => text.style(theme::Text::Destructive).into(),
+                            _ => text.into(),
+                        }
+                    })
                     .collect()
             };
 
@@ -196,13 +241,31 @@ where
             )
             .padding(15);
 
-            let console_col = column(vec![
-                scrollable.into(),
+            let mut kill_button = button(Text::new("Force Kill").size(20))
+                .padding([5, 15])
+                .style(theme::Button::Destructive);
+
+            if self.status {
+                kill_button = kill_button.on_press(CardMessage::KillServer);
+            }
+
+            let edit_button = button(Text::new("Edit").size(20))
+                .padding([5, 15])
+                .on_press(CardMessage::EditServer);
+
+            let input_row = row!(
                 text_input("Enter a command", &state.command)
                     .on_input(CardMessage::UpdateCommand)
                     .on_submit(CardMessage::SubmitCommand)
-                    .size(20)
-                    .into(),
+                    .size(20),
+                edit_button,
+                kill_button
+            )
+            .align_items(Alignment::Center);
+
+            let console_col = column(vec![
+                scrollable.into(),
+                input_row.into(),
                 Space::new(0.0, Length::Fixed(30.0)).into(),
             ]);
 
@@ -8,6 +8,7 @@ mod servers;
 mod settings;
 mod tab_nav;
 mod theme;
+mod trust;
 mod views;
 
 use std::time::Duration;
@@ -29,11 +30,14 @@ use request::Request;
 use servers::Servers;
 use settings::Settings;
 use theme::Theme;
+use trust::Trust;
 
 use uuid::Uuid;
 use views::{
+    editor::{self, EditorState},
     home::{self, MainState},
     login::{self, LoginState},
+    password::{self, PasswordState},
     settings::SettingsState,
 };
 
@@ -53,6 +57,8 @@ pub enum Message {
     LoginPage(login::Event),
     HomePage(home::Event),
     SettingsPage(views::settings::Event),
+    PasswordPage(password::Event),
+    EditorPage(editor::Event),
 
     Event(Event),
 
@@ -62,7 +68,7 @@ pub enum Message {
     GotoPage(Page),
 
     Login(String, String, String),
-    LoggedIn(Uuid, String, String),
+    LoggedIn(Uuid, bool, String, String),
 
     FontLoaded(Result<(), font::Error>),
 
@@ -84,6 +90,8 @@ pub enum Page {
     Login(LoginState),
     Main(MainState),
     Settings(SettingsState),
+    Password(PasswordState),
+    Editor(EditorState),
 }
 
 impl Default for Page {
@@ -107,6 +115,14 @@ impl Page {
                 width: 768,
                 height: 768,
             }),
+            Page::Password(_) => Some(Size {
+                width: 512,
+                height: 768,
+            }),
+            Page::Editor(_) => Some(Size {
+                width: 768,
+                height: 768,
+            }),
         }
     }
 }
@@ -117,6 +133,19 @@ struct App {
     status_bar: Status,
     login_cache: Cache,
     settings: Settings,
+    trust: Trust,
+}
+
+impl App {
+    fn trust_certificates(&mut self) {
+        let fingerprints = self.trust.fingerprints();
+
+        if fingerprints != self.login_cache.fingerprints {
+            self.login_cache.fingerprints = fingerprints;
+
+            let _ = self.login_cache.save();
+        }
+    }
 }
 
 impl Application for App {
@@ -140,6 +169,7 @@ impl Application for App {
                 }),
                 previous_page: None,
                 status_bar: Status::None,
+                trust: Trust::new(login_cache.fingerprints.clone()),
                 login_cache,
                 settings,
             },
@@ -199,7 +229,41 @@ impl Application for App {
                     return Command::batch(commands);
                 };
 
-                let msg = state.update(e);
+                let (msg, cmd) = state.update(e);
+
+                commands.push(cmd.map(Message::SettingsPage));
+
+                if let Some(m) = msg {
+                    let command = self.update(m);
+
+                    commands.push(command);
+                }
+            }
+
+            Message::PasswordPage(e) => {
+                let Page::Password(state) = &mut self.page else {
+                    return Command::batch(commands);
+                };
+
+                let (msg, cmd) = state.update(e);
+
+                commands.push(cmd.map(Message::PasswordPage));
+
+                if let Some(m) = msg {
+                    let command = self.update(m);
+
+                    commands.push(command);
+                }
+            }
+
+            Message::EditorPage(e) => {
+                let Page::Editor(state) = &mut self.page else {
+                    return Command::batch(commands);
+                };
+
+                let (msg, cmd) = state.update(e);
+
+                commands.push(cmd.map(Message::EditorPage));
 
                 if let Some(m) = msg {
                     let command = self.update(m);
@@ -234,11 +298,12 @@ impl Application for App {
             }
 
             Message::Login(address, username, password) => {
-                self.login_cache = Cache::new(address.clone(), username.clone());
+                self.login_cache =
+                    Cache::new(address.clone(), username.clone(), self.trust.fingerprints());
 
                 let _ = self.login_cache.save();
 
-                let request = Request::new(address.clone());
+                let request = Request::new(address.clone(), &self.trust);
                 let request_ = request.clone();
 
                 let (username, password) = (username.clone(), password.clone());
@@ -258,15 +323,30 @@ impl Application for App {
 
                 commands.push(Command::perform(
                     async move { request.get_token(username, password).await },
-                    move |i| match i {
-                        Some(status) => Message::LoggedIn(status, address, username_),
-                        None => Message::Error("Failed to login".to_string()),
+                    move |i| match i.map(|i| (i.token, i.must_change_password)) {
+                        Ok((Some(token), must_change_password)) => {
+                            Message::LoggedIn(token, must_change_password, address, username_)
+                        }
+                        Ok((None, _)) => Message::Error("Failed to login".to_string()),
+                        Err(e) => Message::Error(e),
                     },
                 ));
             }
 
-            Message::LoggedIn(token, address, username) => {
-                let request = Request::new(address.clone());
+            Message::LoggedIn(token, true, address, username) => {
+                self.trust_certificates();
+
+                let request = Request::new(address.clone(), &self.trust);
+
+                let page = Page::Password(PasswordState::new(request, address, username, token));
+
+                commands.push(self.update(Message::GotoPage(page)));
+            }
+
+            Message::LoggedIn(token, false, address, username) => {
+                self.trust_certificates();
+
+                let request = Request::new(address.clone(), &self.trust);
                 let username = username.clone();
 
                 let request_ = request.clone();
@@ -283,6 +363,7 @@ impl Application for App {
                             username,
                             token,
                             servers: i,
+                            system: None,
                         })),
                         None => Message::Error("Failed to load status".to_string()),
                     },
@@ -290,6 +371,19 @@ impl Application for App {
             }
 
             Message::Logout => {
+                let session = match &self.page {
+                    Page::Main(state) => Some((state.request.clone(), state.token)),
+                    Page::Password(state) => Some((state.request.clone(), state.token)),
+                    _ => None,
+                };
+
+                if let Some((request, token)) = session {
+                    commands.push(Command::perform(
+                        async move { request.logout(token).await },
+                        |_| Message::None,
+                    ));
+                }
+
                 let mut login_state = LoginState::default();
 
                 login_state.username = self.login_cache.last_username.clone();
@@ -314,6 +408,13 @@ impl Application for App {
                             commands.push(text_input::focus(state.tab_nav.next()));
                         }
                     }
+                    Page::Password(state) => {
+                        if modifiers.shift() {
+                            commands.push(text_input::focus(state.tab_nav.back()));
+                        } else {
+                            commands.push(text_input::focus(state.tab_nav.next()));
+                        }
+                    }
                     _ => {}
                 };
             }
@@ -363,6 +464,8 @@ impl Application for App {
             Page::Login(s) => s.view().map(Message::LoginPage),
             Page::Main(s) => s.view().map(Message::HomePage),
             Page::Settings(s) => s.view(&self.settings).map(Message::SettingsPage),
+            Page::Password(s) => s.view().map(Message::PasswordPage),
+            Page::Editor(s) => s.view().map(Message::EditorPage),
         };
 
         iced::widget::column!(page, status_bar(&self.status_bar)).into()
@@ -1,10 +1,14 @@
 use models::{
-    FromJson, GlobalStatus, InputCommandRequest, ServerOutput, ToJson, TokenRequest, TokenResponse,
+    AuditEntry, CreateUserRequest, ErrorResponse, FromJson, GlobalStatus, InputCommandRequest,
+    PasswordChangeRequest, ServerDefinition, ServerEvent, ServerMetrics, ServerOutput,
+    ServerUpdate, SystemOverview, ToJson, TokenRequest, TokenResponse, UpdateUserRequest, UserInfo,
 };
-use reqwest::{Client, Method};
+use reqwest::{Client, Method, Response};
 
 use uuid::Uuid;
 
+use crate::trust::Trust;
+
 #[allow(unused)]
 #[derive(Debug)]
 pub enum Error {
@@ -34,17 +38,30 @@ pub struct Request {
 }
 
 impl Request {
-    pub fn new(address: String) -> Self {
+    pub fn new(address: String, trust: &Trust) -> Self {
+        let (scheme, host) = address
+            .split_once("://")
+            .map(|(scheme, host)| (scheme.to_string(), host.to_string()))
+            .unwrap_or(("http".to_string(), address));
+
+        let client = match scheme.as_str() {
+            "https" => Client::builder()
+                .use_preconfigured_tls(trust.tls_config(&host))
+                .build()
+                .unwrap_or_default(),
+            _ => Client::new(),
+        };
+
         Self {
-            client: reqwest::Client::new(),
-            address,
+            client,
+            address: format!("{}://{}", scheme, host),
         }
     }
 
     pub async fn get_version(&self) -> Option<String> {
         let req = self
             .client
-            .request(Method::GET, format!("http://{}/api/version", self.address));
+            .request(Method::GET, format!("{}/api/version", self.address));
 
         let res = req.send().await.ok()?;
 
@@ -52,12 +69,10 @@ impl Request {
     }
 
     pub async fn get_status(&self, token: Uuid) -> Option<GlobalStatus> {
-        let token = serde_json::to_string(&token).unwrap();
-
         let res = self
             .client
-            .request(Method::GET, format!("http://{}/api/status", self.address))
-            .header("authorization", token)
+            .request(Method::GET, format!("{}/api/status", self.address))
+            .bearer_auth(token)
             .send()
             .await
             .ok()?;
@@ -67,16 +82,24 @@ impl Request {
         GlobalStatus::from_json(body)
     }
 
-    pub async fn get_output(&self, server_id: String, token: Uuid) -> Option<ServerOutput> {
-        let token = serde_json::to_string(&token).unwrap();
+    pub async fn get_output(
+        &self,
+        server_id: String,
+        since: Option<u64>,
+        token: Uuid,
+    ) -> Option<ServerOutput> {
+        let query = match since {
+            Some(seq) => format!("?since={}", seq),
+            None => String::new(),
+        };
 
         let res = self
             .client
             .request(
                 Method::GET,
-                format!("http://{}/api/server/output/{}", self.address, server_id),
+                format!("{}/api/server/output/{}{}", self.address, server_id, query),
             )
-            .header("authorization", token)
+            .bearer_auth(token)
             .send()
             .await
             .ok()?;
@@ -86,16 +109,180 @@ impl Request {
         ServerOutput::from_json(body)
     }
 
-    pub async fn start_server(&self, server_id: String, token: Uuid) -> bool {
-        let token = serde_json::to_string(&token).unwrap();
+    pub async fn get_system(&self, token: Uuid) -> Option<SystemOverview> {
+        let res = self
+            .client
+            .request(Method::GET, format!("{}/api/system", self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .ok()?;
+
+        let body = res.text().await.ok()?;
+
+        SystemOverview::from_json(body)
+    }
 
+    pub async fn get_metrics(&self, server_id: String, token: Uuid) -> Option<ServerMetrics> {
+        let res = self
+            .client
+            .request(
+                Method::GET,
+                format!("{}/api/server/metrics/{}", self.address, server_id),
+            )
+            .bearer_auth(token)
+            .send()
+            .await
+            .ok()?;
+
+        let body = res.text().await.ok()?;
+
+        ServerMetrics::from_json(body)
+    }
+
+    pub async fn poll(
+        &self,
+        server_id: String,
+        since: Option<u64>,
+        running: Option<bool>,
+        token: Uuid,
+    ) -> Option<Vec<ServerEvent>> {
+        let mut query = vec![];
+
+        if let Some(seq) = since {
+            query.push(format!("since={}", seq));
+        }
+
+        if let Some(running) = running {
+            query.push(format!("running={}", running));
+        }
+
+        let res = self
+            .client
+            .request(
+                Method::GET,
+                format!(
+                    "{}/api/server/poll/{}?{}",
+                    self.address,
+                    server_id,
+                    query.join("&")
+                ),
+            )
+            .bearer_auth(token)
+            .send()
+            .await
+            .ok()?;
+
+        if res.status() != 200 {
+            return None;
+        }
+
+        let body = res.text().await.ok()?;
+
+        Vec::<ServerEvent>::from_json(body)
+    }
+
+    pub async fn start_server(&self, server_id: String, token: Uuid) -> Result<(), String> {
         let res = self
             .client
             .request(
                 Method::POST,
-                format!("http://{}/api/server/start/{}", self.address, server_id),
+                format!("{}/api/server/start/{}", self.address, server_id),
             )
-            .header("authorization", token)
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to start server").await
+    }
+
+    pub async fn get_server_config(
+        &self,
+        server_id: String,
+        token: Uuid,
+    ) -> Result<ServerDefinition, String> {
+        let res = self
+            .client
+            .request(
+                Method::GET,
+                format!("{}/api/server/config/{}", self.address, server_id),
+            )
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status() != 200 {
+            return Err(format!("Failed to load server config ({})", res.status()));
+        }
+
+        let body = res.text().await.map_err(|e| e.to_string())?;
+
+        ServerDefinition::from_json(body).ok_or(Error::ParseError.to_string())
+    }
+
+    pub async fn create_server(
+        &self,
+        definition: ServerDefinition,
+        token: Uuid,
+    ) -> Result<(), String> {
+        let res = self
+            .client
+            .request(Method::POST, format!("{}/api/server/create", self.address))
+            .bearer_auth(token)
+            .body(definition.to_json())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to create server").await
+    }
+
+    pub async fn update_server(
+        &self,
+        server_id: String,
+        update: ServerUpdate,
+        token: Uuid,
+    ) -> Result<(), String> {
+        let res = self
+            .client
+            .request(
+                Method::POST,
+                format!("{}/api/server/update/{}", self.address, server_id),
+            )
+            .bearer_auth(token)
+            .body(update.to_json())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to update server").await
+    }
+
+    pub async fn remove_server(&self, server_id: String, token: Uuid) -> Result<(), String> {
+        let res = self
+            .client
+            .request(
+                Method::POST,
+                format!("{}/api/server/remove/{}", self.address, server_id),
+            )
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to remove server").await
+    }
+
+    pub async fn stop_server(&self, server_id: String, token: Uuid) -> bool {
+        let res = self
+            .client
+            .request(
+                Method::POST,
+                format!("{}/api/server/stop/{}", self.address, server_id),
+            )
+            .bearer_auth(token)
             .send()
             .await;
 
@@ -105,16 +292,14 @@ impl Request {
         }
     }
 
-    pub async fn stop_server(&self, server_id: String, token: Uuid) -> bool {
-        let token = serde_json::to_string(&token).unwrap();
-
+    pub async fn kill_server(&self, server_id: String, token: Uuid) -> bool {
         let res = self
             .client
             .request(
                 Method::POST,
-                format!("http://{}/api/server/stop/{}", self.address, server_id),
+                format!("{}/api/server/kill/{}", self.address, server_id),
             )
-            .header("authorization", token)
+            .bearer_auth(token)
             .send()
             .await;
 
@@ -125,14 +310,13 @@ impl Request {
     }
 
     pub async fn send_command(&self, server_id: String, command: String, token: Uuid) -> bool {
-        let token = serde_json::to_string(&token).unwrap();
-
-        let res = reqwest::Client::new()
+        let res = self
+            .client
             .request(
                 Method::POST,
-                format!("http://{}/api/server/input/{}", self.address, server_id),
+                format!("{}/api/server/input/{}", self.address, server_id),
             )
-            .header("authorization", token)
+            .bearer_auth(token)
             .body(InputCommandRequest { command }.to_json())
             .send()
             .await;
@@ -143,16 +327,207 @@ impl Request {
         }
     }
 
-    pub async fn get_token(&self, username: String, password: String) -> Option<Uuid> {
-        let res = reqwest::Client::new()
-            .request(Method::GET, format!("http://{}/api/auth/", self.address))
+    pub async fn change_password(
+        &self,
+        current_password: String,
+        new_password: String,
+        token: Uuid,
+    ) -> Result<(), String> {
+        let res = self
+            .client
+            .request(
+                Method::POST,
+                format!("{}/api/account/password", self.address),
+            )
+            .bearer_auth(token)
+            .body(
+                PasswordChangeRequest {
+                    current_password,
+                    new_password,
+                }
+                .to_json(),
+            )
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match res.status().as_u16() {
+            403 => Err("Current password is incorrect".to_string()),
+            _ => expect_ok(res, "Failed to change password").await,
+        }
+    }
+
+    pub async fn list_users(&self, token: Uuid) -> Result<Vec<UserInfo>, String> {
+        let res = self
+            .client
+            .request(Method::GET, format!("{}/api/users/list", self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status() != 200 {
+            return Err(format!("Failed to load users ({})", res.status()));
+        }
+
+        let body = res.text().await.map_err(|e| e.to_string())?;
+
+        Vec::<UserInfo>::from_json(body).ok_or(Error::ParseError.to_string())
+    }
+
+    pub async fn create_user(&self, user: CreateUserRequest, token: Uuid) -> Result<(), String> {
+        let res = self
+            .client
+            .request(Method::POST, format!("{}/api/users/create", self.address))
+            .bearer_auth(token)
+            .body(user.to_json())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to create user").await
+    }
+
+    pub async fn update_user(
+        &self,
+        user_id: String,
+        update: UpdateUserRequest,
+        token: Uuid,
+    ) -> Result<(), String> {
+        let res = self
+            .client
+            .request(
+                Method::POST,
+                format!("{}/api/users/update/{}", self.address, user_id),
+            )
+            .bearer_auth(token)
+            .body(update.to_json())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to update user").await
+    }
+
+    pub async fn delete_user(&self, user_id: String, token: Uuid) -> Result<(), String> {
+        let res = self
+            .client
+            .request(
+                Method::POST,
+                format!("{}/api/users/delete/{}", self.address, user_id),
+            )
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        expect_ok(res, "Failed to delete user").await
+    }
+
+    pub async fn get_audit(
+        &self,
+        user: Option<String>,
+        action: Option<String>,
+        token: Uuid,
+    ) -> Result<Vec<AuditEntry>, String> {
+        let mut query = vec![];
+
+        if let Some(user) = user {
+            query.push(("user", user));
+        }
+
+        if let Some(action) = action {
+            query.push(("action", action));
+        }
+
+        let res = self
+            .client
+            .request(Method::GET, format!("{}/api/audit", self.address))
+            .query(&query)
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status() != 200 {
+            return Err(format!("Failed to load audit log ({})", res.status()));
+        }
+
+        let body = res.text().await.map_err(|e| e.to_string())?;
+
+        Vec::<AuditEntry>::from_json(body).ok_or(Error::ParseError.to_string())
+    }
+
+    pub async fn refresh_session(&self, token: Uuid) -> bool {
+        let res = self
+            .client
+            .request(Method::POST, format!("{}/api/auth/refresh", self.address))
+            .bearer_auth(token)
+            .send()
+            .await;
+
+        match res {
+            Ok(r) if r.status() == 200 => true,
+            _ => false,
+        }
+    }
+
+    pub async fn logout(&self, token: Uuid) -> bool {
+        let res = self
+            .client
+            .request(Method::POST, format!("{}/api/auth/logout", self.address))
+            .bearer_auth(token)
+            .send()
+            .await;
+
+        match res {
+            Ok(r) if r.status() == 200 => true,
+            _ => false,
+        }
+    }
+
+    pub async fn get_token(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<TokenResponse, String> {
+        let res = self
+            .client
+            .request(Method::GET, format!("{}/api/auth/", self.address))
             .body(TokenRequest { username, password }.to_json())
             .send()
             .await
-            .ok()?;
+            .map_err(|e| e.to_string())?;
 
-        let body = res.text().await.ok()?;
+        let status = res.status();
+
+        let body = res.text().await.map_err(|e| e.to_string())?;
+
+        if status == 429 {
+            return Err(ErrorResponse::from_json(body)
+                .map(|i| i.error)
+                .unwrap_or_else(|| "Too many failed logins".to_string()));
+        }
 
-        TokenResponse::from_json(body).and_then(|i| i.token)
+        TokenResponse::from_json(body)
+            .filter(|i| i.token.is_some())
+            .ok_or("Failed to login".to_string())
     }
 }
+
+async fn expect_ok(res: Response, action: &str) -> Result<(), String> {
+    let status = res.status();
+
+    if status == 200 {
+        return Ok(());
+    }
+
+    let error = res
+        .text()
+        .await
+        .ok()
+        .and_then(ErrorResponse::from_json)
+        .map(|i| i.error);
+
+    Err(error.unwrap_or_else(|| format!("{} ({})", action, status)))
+}
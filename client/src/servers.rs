@@ -1,11 +1,20 @@
+use chrono::{DateTime, Local};
 use indexmap::IndexMap;
-use models::{ServerStatus, GlobalStatus};
+use models::{ConsoleLine, GlobalStatus, ServerMetrics, ServerOutput, ServerStatus};
+
+const SPARKLINE: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
 #[derive(Debug, Clone)]
 pub struct Server {
     pub id: String,
     pub running: bool,
-    pub output: Vec<String>,
+    pub scrollback: usize,
+    pub restarts: u32,
+    pub last_exit_code: Option<i32>,
+    pub last_exit_reason: Option<String>,
+    pub stopped_at: Option<u64>,
+    pub metrics: Option<ServerMetrics>,
+    pub output: Vec<ConsoleLine>,
 }
 
 impl From<ServerStatus> for Server {
@@ -13,6 +22,12 @@ impl From<ServerStatus> for Server {
         Server {
             id: value.id,
             running: value.running,
+            scrollback: value.scrollback,
+            restarts: value.restarts,
+            last_exit_code: value.last_exit_code,
+            last_exit_reason: value.last_exit_reason,
+            stopped_at: value.stopped_at,
+            metrics: None,
             output: Vec::new(),
         }
     }
@@ -21,6 +36,85 @@ impl From<ServerStatus> for Server {
 impl Server {
     pub fn update(&mut self, server_status: ServerStatus) {
         self.running = server_status.running;
+        self.scrollback = server_status.scrollback;
+        self.restarts = server_status.restarts;
+        self.last_exit_code = server_status.last_exit_code;
+        self.last_exit_reason = server_status.last_exit_reason;
+        self.stopped_at = server_status.stopped_at;
+    }
+
+    pub fn exit_summary(&self) -> Option<String> {
+        if self.running {
+            return None;
+        }
+
+        let reason = match self.last_exit_code {
+            Some(code) => format!("exited with code {}", code),
+            None => self.last_exit_reason.clone()?,
+        };
+
+        let at = self
+            .stopped_at
+            .and_then(|i| DateTime::from_timestamp(i as i64, 0))
+            .map(|i| i.with_timezone(&Local).format("%H:%M").to_string());
+
+        match at {
+            Some(at) => Some(format!("{} at {}", reason, at)),
+            None => Some(reason),
+        }
+    }
+
+    pub fn metrics_summary(&self) -> Option<String> {
+        let metrics = self.metrics.as_ref().filter(|_| self.running)?;
+
+        let sample = metrics.samples.last()?;
+
+        let uptime = metrics.uptime.unwrap_or(0);
+
+        Some(format!(
+            "CPU {:.1}%  RSS {}  Up {}",
+            sample.cpu_percent,
+            format_bytes(sample.rss_bytes),
+            format_duration(uptime)
+        ))
+    }
+
+    pub fn sparkline(&self) -> Option<String> {
+        let metrics = self.metrics.as_ref().filter(|_| self.running)?;
+
+        let max = metrics
+            .samples
+            .iter()
+            .map(|i| i.cpu_percent)
+            .fold(1.0, f32::max);
+
+        let line = metrics
+            .samples
+            .iter()
+            .map(|i| {
+                let level = (i.cpu_percent / max * (SPARKLINE.len() - 1) as f32).round() as usize;
+
+                SPARKLINE[level.min(SPARKLINE.len() - 1)]
+            })
+            .collect();
+
+        Some(line)
+    }
+
+    pub fn append(&mut self, server_output: ServerOutput) {
+        let Some(output) = server_output.output else {
+            return;
+        };
+
+        if server_output.reset {
+            self.output.clear();
+        }
+
+        self.output.extend(output);
+
+        let excess = self.output.len().saturating_sub(self.scrollback);
+
+        self.output.drain(..excess);
     }
 }
 
@@ -75,3 +169,27 @@ impl Servers {
         }
     }
 }
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+pub fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+
+    match (days, hours) {
+        (0, 0) => format!("{}m {}s", minutes, secs % 60),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
@@ -82,6 +82,7 @@ pub enum Text {
     #[default]
     Default,
     Hint,
+    Destructive,
 }
 
 impl text::StyleSheet for Theme {
@@ -91,6 +92,7 @@ impl text::StyleSheet for Theme {
         let color = match style {
             Text::Default => Color::WHITE,
             Text::Hint => self.palette.hint,
+            Text::Destructive => self.palette.destructive,
         };
 
         text::Appearance { color: Some(color) }
@@ -0,0 +1,68 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, Error, ServerName,
+};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Default)]
+pub struct Trust(Arc<Mutex<HashMap<String, String>>>);
+
+impl Trust {
+    pub fn new(fingerprints: HashMap<String, String>) -> Self {
+        Self(Arc::new(Mutex::new(fingerprints)))
+    }
+
+    pub fn fingerprints(&self) -> HashMap<String, String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn tls_config(&self, address: &str) -> ClientConfig {
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(Pinned {
+                address: address.to_string(),
+                trust: self.clone(),
+            }))
+            .with_no_client_auth()
+    }
+}
+
+struct Pinned {
+    address: String,
+    trust: Trust,
+}
+
+impl ServerCertVerifier for Pinned {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let fingerprint = format!("{:x}", Sha256::digest(&end_entity.0));
+
+        let mut fingerprints = self.trust.0.lock().unwrap();
+
+        match fingerprints.get(&self.address) {
+            Some(trusted) if *trusted != fingerprint => Err(Error::General(format!(
+                "Certificate for {} changed, expected {} but got {}",
+                self.address, trusted, fingerprint
+            ))),
+            Some(_) => Ok(ServerCertVerified::assertion()),
+            None => {
+                fingerprints.insert(self.address.clone(), fingerprint);
+
+                Ok(ServerCertVerified::assertion())
+            }
+        }
+    }
+}
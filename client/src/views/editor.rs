@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+
+use iced::{
+    widget::{button, column, image::Handle, row, text_input, Image, Space, Text},
+    Alignment, Command, Length,
+};
+use models::{ServerDefinition, ServerUpdate};
+use uuid::Uuid;
+
+use crate::{components::icon_button, request::Request, theme, Element, Message, BACK_ARROW};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorField {
+    Id,
+    Display,
+    StartCommand,
+    Env,
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Super(Box<Message>),
+    GotoPrevious,
+
+    UpdateEditorInput(EditorField, String),
+    Save,
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+pub struct EditorState {
+    request: Request,
+    token: Uuid,
+
+    original: Option<ServerDefinition>,
+
+    id: String,
+    display: String,
+    start_command: String,
+    env: String,
+}
+
+impl EditorState {
+    pub fn create(request: Request, token: Uuid) -> Self {
+        Self {
+            request,
+            token,
+
+            original: None,
+
+            id: String::new(),
+            display: String::new(),
+            start_command: String::new(),
+            env: String::new(),
+        }
+    }
+
+    pub fn edit(request: Request, token: Uuid, definition: ServerDefinition) -> Self {
+        Self {
+            request,
+            token,
+
+            id: definition.id.clone(),
+            display: definition.display.clone(),
+            start_command: definition.start_command.clone(),
+            env: format_env(&definition.env),
+
+            original: Some(definition),
+        }
+    }
+
+    pub fn update(&mut self, evt: Event) -> (Option<Message>, Command<Event>) {
+        let mut msg = None;
+        let mut commands = vec![];
+
+        match evt {
+            Event::Super(m) => msg = Some(*m),
+            Event::GotoPrevious => msg = Some(Message::GotoPrevious),
+            Event::UpdateEditorInput(field, value) => match field {
+                EditorField::Id => self.id = value,
+                EditorField::Display => self.display = value,
+                EditorField::StartCommand => self.start_command = value,
+                EditorField::Env => self.env = value,
+            },
+            Event::Save => {
+                let env = match parse_env(&self.env) {
+                    Ok(env) => env,
+                    Err(e) => return (Some(Message::Error(e)), Command::none()),
+                };
+
+                let request = self.request.clone();
+                let token = self.token;
+
+                match &self.original {
+                    Some(original) => {
+                        let update = ServerUpdate {
+                            display: Some(self.display.clone()).filter(|i| *i != original.display),
+                            start_command: Some(self.start_command.clone())
+                                .filter(|i| *i != original.start_command),
+                            env: Some(env).filter(|i| *i != original.env),
+                        };
+
+                        let server_id = original.id.clone();
+
+                        commands.push(Command::perform(
+                            async move { request.update_server(server_id, update, token).await },
+                            saved,
+                        ));
+                    }
+                    None => {
+                        let definition = ServerDefinition {
+                            id: self.id.clone(),
+                            display: self.display.clone(),
+                            start_command: self.start_command.clone(),
+                            env,
+                        };
+
+                        commands.push(Command::perform(
+                            async move { request.create_server(definition, token).await },
+                            saved,
+                        ));
+                    }
+                }
+            }
+            Event::Remove => {
+                let Some(original) = &self.original else {
+                    return (msg, Command::none());
+                };
+
+                let request = self.request.clone();
+                let (server_id, token) = (original.id.clone(), self.token);
+
+                commands.push(Command::perform(
+                    async move { request.remove_server(server_id, token).await },
+                    saved,
+                ));
+            }
+        }
+
+        (msg, Command::batch(commands))
+    }
+
+    pub fn view<'a>(&self) -> Element<'a, Event> {
+        let back_button =
+            icon_button(Image::new(Handle::from_memory(BACK_ARROW))).on_press(Event::GotoPrevious);
+
+        let title = match &self.original {
+            Some(original) => format!("Edit {}", original.id),
+            None => "New Server".to_string(),
+        };
+
+        let nav_row = row!(back_button, Text::new(title).size(30))
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .padding(20);
+
+        let field = |name: &str, placeholder: &str, value: &str, target| {
+            column!(
+                Text::new(name.to_string())
+                    .size(20)
+                    .style(theme::Text::Hint),
+                text_input(placeholder, value)
+                    .on_input(move |s| Event::UpdateEditorInput(target, s))
+                    .padding([10, 25])
+                    .size(24.0)
+            )
+            .spacing(5)
+        };
+
+        let mut id = text_input("Id", &self.id).padding([10, 25]).size(24.0);
+
+        if self.original.is_none() {
+            id = id.on_input(|s| Event::UpdateEditorInput(EditorField::Id, s));
+        }
+
+        let id = column!(Text::new("Id").size(20).style(theme::Text::Hint), id).spacing(5);
+
+        let display = field(
+            "Display name",
+            "Display",
+            &self.display,
+            EditorField::Display,
+        );
+        let start_command = field(
+            "Start command",
+            "java -jar server.jar",
+            &self.start_command,
+            EditorField::StartCommand,
+        );
+        let env = field(
+            "Environment (KEY=VALUE; KEY=VALUE)",
+            "KEY=VALUE",
+            &self.env,
+            EditorField::Env,
+        );
+
+        let mut buttons = row!(button(Text::new("Save").size(24))
+            .on_press(Event::Save)
+            .padding([10, 25]))
+        .spacing(20);
+
+        if self.original.is_some() {
+            buttons = buttons.push(
+                button(Text::new("Remove").size(24))
+                    .on_press(Event::Remove)
+                    .style(theme::Button::Destructive)
+                    .padding([10, 25]),
+            );
+        }
+
+        let form = column!(id, display, start_command, env, buttons)
+            .spacing(20)
+            .padding([0, 60]);
+
+        column!(nav_row, form, Space::new(Length::Fill, Length::Fill))
+            .height(Length::Fill)
+            .into()
+    }
+}
+
+fn saved(result: Result<(), String>) -> Event {
+    match result {
+        Ok(()) => Event::GotoPrevious,
+        Err(e) => Event::Super(Box::new(Message::Error(e))),
+    }
+}
+
+fn format_env(env: &BTreeMap<String, String>) -> String {
+    env.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn parse_env(value: &str) -> Result<BTreeMap<String, String>, String> {
+    value
+        .split(';')
+        .map(|i| i.trim())
+        .filter(|i| !i.is_empty())
+        .map(|i| match i.split_once('=') {
+            Some((k, v)) if !k.trim().is_empty() => Ok((k.trim().to_string(), v.to_string())),
+            _ => Err(format!("Invalid environment entry {:?}", i)),
+        })
+        .collect()
+}
@@ -1,13 +1,13 @@
 use std::time::Duration;
 
-use models::ServerOutput;
+use models::{ServerEvent, ServerMetrics, ServerOutput, ServerStatus, SystemOverview};
 use uuid::Uuid;
 
 use crate::{
     cache::Cache,
     components::{icon_button, navbar, Card},
     request::Request,
-    servers::Servers,
+    servers::{format_bytes, format_duration, Servers},
     theme, Element, Message, Page, LOGOUT_BUTTON, SETTINGS_BUTTON,
 };
 
@@ -17,7 +17,13 @@ use iced::{
     Alignment, Command, Length, Subscription,
 };
 
-use super::{login::LoginState, settings::SettingsState};
+use super::{editor::EditorState, login::LoginState, settings::SettingsState};
+
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+const FALLBACK_POLLS: u32 = 30;
+const METRICS_INTERVAL: Duration = Duration::from_secs(2);
+const SYSTEM_INTERVAL: Duration = Duration::from_secs(5);
+const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(900);
 
 #[derive(Debug, Clone)]
 pub struct MainState {
@@ -26,15 +32,21 @@ pub struct MainState {
 
     pub token: Uuid,
     pub servers: Servers,
+    pub system: Option<SystemOverview>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Event {
     Super(Box<Message>),
     ToggleServer(String),
+    KillServer(String),
+    EditServer(String),
+    AddServer,
     SendCommand(String, String),
     StatusRefreshed(models::GlobalStatus),
-    OutputRefreshed(String, Vec<String>),
+    ServerUpdated(String, Option<ServerStatus>, Option<ServerOutput>),
+    MetricsRefreshed(ServerMetrics),
+    SystemRefreshed(SystemOverview),
     None,
     Logout,
 }
@@ -49,11 +61,23 @@ impl MainState {
                 Event::Super(m) => msg = Some(*m),
                 Event::Logout => msg = Some(Message::Logout),
                 Event::StatusRefreshed(id) => self.servers.update(id),
-                Event::OutputRefreshed(id, new) => {
+                Event::ServerUpdated(id, status, output) => {
                     if let Some(server) = self.servers.inner.get_mut(&id) {
-                        server.output = new;
+                        if let Some(status) = status {
+                            server.update(status);
+                        }
+
+                        if let Some(output) = output {
+                            server.append(output);
+                        }
+                    }
+                }
+                Event::MetricsRefreshed(metrics) => {
+                    if let Some(server) = self.servers.inner.get_mut(&metrics.id) {
+                        server.metrics = Some(metrics);
                     }
                 }
+                Event::SystemRefreshed(system) => self.system = Some(system),
                 Event::SendCommand(id, command) => {
                     let request = self.request.clone();
 
@@ -64,6 +88,40 @@ impl MainState {
                         |_i| Event::None,
                     ))
                 }
+                Event::KillServer(server_id) => {
+                    let request = self.request.clone();
+
+                    let token = self.token.clone();
+
+                    commands.push(Command::perform(
+                        async move { request.kill_server(server_id, token).await },
+                        |_i| Event::None,
+                    ))
+                }
+                Event::EditServer(server_id) => {
+                    let request = self.request.clone();
+
+                    let token = self.token;
+
+                    commands.push(Command::perform(
+                        async move {
+                            let definition = request.get_server_config(server_id, token).await;
+
+                            definition.map(|i| EditorState::edit(request, token, i))
+                        },
+                        |i| match i {
+                            Ok(state) => {
+                                Event::Super(Box::new(Message::GotoPage(Page::Editor(state))))
+                            }
+                            Err(e) => Event::Super(Box::new(Message::Error(e))),
+                        },
+                    ))
+                }
+                Event::AddServer => {
+                    let state = EditorState::create(self.request.clone(), self.token);
+
+                    msg = Some(Message::GotoPage(Page::Editor(state)));
+                }
                 Event::ToggleServer(server_id) => {
                     let request = self.request.clone();
 
@@ -80,7 +138,10 @@ impl MainState {
                         )),
                         false => commands.push(Command::perform(
                             async move { request.start_server(server_id, token).await },
-                            |_i| Event::None,
+                            |i| match i {
+                                Ok(()) => Event::None,
+                                Err(e) => Event::Super(Box::new(Message::Error(e))),
+                            },
                         )),
                     };
                 }
@@ -94,10 +155,17 @@ impl MainState {
     pub fn view<'a>(&self) -> Element<'a, Event> {
         let username = Text::new(self.username.clone()).size(30);
 
+        let system = Text::new(self.system_summary())
+            .size(18)
+            .style(theme::Text::Hint);
+
         let settings_icon = Image::new(Handle::from_memory(SETTINGS_BUTTON));
 
         let settings_button = icon_button(settings_icon).on_press(Event::Super(Box::new(
-            Message::GotoPage(Page::Settings(SettingsState::default())),
+            Message::GotoPage(Page::Settings(SettingsState::with_session(
+                self.request.clone(),
+                self.token,
+            ))),
         )));
 
         let logout_icon = Image::new(Handle::from_memory(LOGOUT_BUTTON));
@@ -105,7 +173,7 @@ impl MainState {
         let logout_button = icon_button(logout_icon).on_press(Event::Logout);
 
         let nav = navbar(
-            row!(username, settings_button, logout_button)
+            row!(system, username, settings_button, logout_button)
                 .align_items(Alignment::Center)
                 .spacing(24)
                 .into(),
@@ -119,21 +187,77 @@ impl MainState {
             col = col.push(Element::from(Card {
                 server_id: id.clone(),
                 status: server.running,
+                restarts: server.restarts,
+                exit: server.exit_summary(),
+                metrics: server.metrics_summary(),
+                sparkline: server.sparkline(),
                 console: server.output.clone(),
 
                 toggle: Event::ToggleServer(id.clone()),
+                kill: Event::KillServer(id.clone()),
+                edit: Event::EditServer(id.clone()),
                 send: move |i| Event::SendCommand(id.clone(), i),
             }));
         }
 
+        let add_button = button(Text::new("Add Server").size(20))
+            .on_press(Event::AddServer)
+            .padding([5, 15]);
+
+        col = col.push(row!(add_button).padding(20));
+
         column!(nav, scrollable(col).height(Length::Fill))
             .height(Length::Fill)
             .into()
     }
 
+    fn system_summary(&self) -> String {
+        let Some(system) = &self.system else {
+            return String::new();
+        };
+
+        let mut parts = vec![];
+
+        if let Some([one, five, fifteen]) = system.load_average {
+            parts.push(format!("Load {:.2} {:.2} {:.2}", one, five, fifteen));
+        }
+
+        if let (Some(total), Some(available)) = (system.memory_total, system.memory_available) {
+            parts.push(format!(
+                "Mem {} / {}",
+                format_bytes(total.saturating_sub(available)),
+                format_bytes(total)
+            ));
+        }
+
+        if let (Some(total), Some(available)) = (system.disk_total, system.disk_available) {
+            parts.push(format!(
+                "Disk {} / {}",
+                format_bytes(total.saturating_sub(available)),
+                format_bytes(total)
+            ));
+        }
+
+        parts.push(format!("Up {}", format_duration(system.uptime)));
+
+        parts.join("  ")
+    }
+
     pub fn subscription(&self) -> Subscription<Event> {
         let mut subscriptions = vec![];
 
+        subscriptions.push(subscription::unfold(
+            "refresh_session".to_string(),
+            (self.request.clone(), self.token.clone()),
+            refresh_session,
+        ));
+
+        subscriptions.push(subscription::unfold(
+            "refresh_system".to_string(),
+            (self.request.clone(), self.token.clone()),
+            refresh_system,
+        ));
+
         subscriptions.push(subscription::unfold(
             "refresh_status".to_string(),
             (self.request.clone(), self.token.clone()),
@@ -143,9 +267,24 @@ impl MainState {
         for server in self.servers.inner.values() {
             subscriptions.push(subscription::unfold(
                 server.id.clone(),
-                (server.id.clone(), self.request.clone(), self.token.clone()),
-                refresh_output,
-            ))
+                WatchState {
+                    server_id: server.id.clone(),
+                    request: self.request.clone(),
+                    token: self.token.clone(),
+                    since: None,
+                    running: None,
+                    polls_left: 0,
+                },
+                watch_server,
+            ));
+
+            if server.running {
+                subscriptions.push(subscription::unfold(
+                    format!("{}-metrics", server.id),
+                    (server.id.clone(), self.request.clone(), self.token.clone()),
+                    refresh_metrics,
+                ));
+            }
         }
 
         Subscription::batch(subscriptions)
@@ -153,7 +292,7 @@ impl MainState {
 }
 
 async fn refresh_status(state: (Request, Uuid)) -> (Event, (Request, Uuid)) {
-    tokio::time::sleep(Duration::from_secs(1)).await;
+    tokio::time::sleep(STATUS_INTERVAL).await;
 
     let (request, token) = &state;
 
@@ -169,17 +308,118 @@ async fn refresh_status(state: (Request, Uuid)) -> (Event, (Request, Uuid)) {
     (Event::StatusRefreshed(global_status), state)
 }
 
-async fn refresh_output(state: (String, Request, Uuid)) -> (Event, (String, Request, Uuid)) {
+async fn refresh_session(state: (Request, Uuid)) -> (Event, (Request, Uuid)) {
+    tokio::time::sleep(SESSION_REFRESH_INTERVAL).await;
+
+    let (request, token) = &state;
+
+    match request.refresh_session(token.clone()).await {
+        true => (Event::None, state),
+        false => (Event::Logout, state),
+    }
+}
+
+async fn refresh_system(state: (Request, Uuid)) -> (Event, (Request, Uuid)) {
+    let (request, token) = &state;
+
+    let system = request.get_system(token.clone()).await;
+
+    tokio::time::sleep(SYSTEM_INTERVAL).await;
+
+    match system {
+        Some(system) => (Event::SystemRefreshed(system), state),
+        None => (Event::None, state),
+    }
+}
+
+struct WatchState {
+    server_id: String,
+    request: Request,
+    token: Uuid,
+    since: Option<u64>,
+    running: Option<bool>,
+    polls_left: u32,
+}
+
+impl WatchState {
+    fn advance(&mut self, output: &ServerOutput) {
+        let Some(lines) = &output.output else {
+            return;
+        };
+
+        if output.reset || !lines.is_empty() {
+            self.since = lines.last().map(|i| i.seq);
+        }
+    }
+}
+
+async fn watch_server(mut state: WatchState) -> (Event, WatchState) {
+    if state.polls_left == 0 {
+        let events = state
+            .request
+            .poll(
+                state.server_id.clone(),
+                state.since,
+                state.running,
+                state.token.clone(),
+            )
+            .await;
+
+        match events {
+            Some(events) => {
+                let mut status = None;
+                let mut output = None;
+
+                for event in events {
+                    match event {
+                        ServerEvent::Status(s) => {
+                            state.running = Some(s.running);
+                            status = Some(s);
+                        }
+                        ServerEvent::Output(o) => {
+                            state.advance(&o);
+                            output = Some(o);
+                        }
+                    }
+                }
+
+                return (
+                    Event::ServerUpdated(state.server_id.clone(), status, output),
+                    state,
+                );
+            }
+            None => state.polls_left = FALLBACK_POLLS,
+        }
+    }
+
+    state.polls_left -= 1;
+
     tokio::time::sleep(Duration::from_secs(1)).await;
 
+    let Some(output) = state
+        .request
+        .get_output(state.server_id.clone(), state.since, state.token.clone())
+        .await
+    else {
+        return (Event::None, state);
+    };
+
+    state.advance(&output);
+
+    (
+        Event::ServerUpdated(state.server_id.clone(), None, Some(output)),
+        state,
+    )
+}
+
+async fn refresh_metrics(state: (String, Request, Uuid)) -> (Event, (String, Request, Uuid)) {
+    tokio::time::sleep(METRICS_INTERVAL).await;
+
     let (server_id, request, token) = &state;
 
-    let Some(ServerOutput {
-        output: Some(output),
-    }) = request.get_output(server_id.clone(), token.clone()).await
-    else {
+    let Some(metrics) = request.get_metrics(server_id.clone(), token.clone()).await else {
         return (Event::None, state);
     };
 
-    (Event::OutputRefreshed(server_id.clone(), output), state)
+    (Event::MetricsRefreshed(metrics), state)
 }
@@ -1,3 +1,5 @@
+pub mod editor;
 pub mod home;
 pub mod login;
+pub mod password;
 pub mod settings;
@@ -0,0 +1,174 @@
+use iced::{
+    widget::{button, column, image::Handle, text_input, Image, Space, Text},
+    Alignment, Command, Length,
+};
+use uuid::Uuid;
+
+use crate::{
+    components::{icon_button, navbar},
+    request::Request,
+    tab_nav::TabNav,
+    theme, Element, Message, LOGOUT_BUTTON,
+};
+
+#[derive(Hash, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordField {
+    Current,
+    New,
+    Confirm,
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Super(Box<Message>),
+    UpdatePasswordInput(PasswordField, String),
+    SubmitPassword,
+
+    Nav(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordState {
+    pub request: Request,
+    pub address: String,
+    pub username: String,
+    pub token: Uuid,
+
+    pub current: String,
+    pub new: String,
+    pub confirm: String,
+
+    pub tab_nav: TabNav,
+}
+
+impl PasswordState {
+    pub fn new(request: Request, address: String, username: String, token: Uuid) -> Self {
+        Self {
+            request,
+            address,
+            username,
+            token,
+
+            current: String::new(),
+            new: String::new(),
+            confirm: String::new(),
+
+            tab_nav: TabNav::new(vec![
+                text_input::Id::new("current"),
+                text_input::Id::new("new"),
+                text_input::Id::new("confirm"),
+            ]),
+        }
+    }
+
+    pub fn update(&mut self, evt: Event) -> (Option<Message>, Command<Event>) {
+        let mut msg = None;
+        let mut commands = vec![];
+
+        match evt {
+            Event::Super(m) => msg = Some(*m),
+            Event::UpdatePasswordInput(field, value) => match field {
+                PasswordField::Current => self.current = value,
+                PasswordField::New => self.new = value,
+                PasswordField::Confirm => self.confirm = value,
+            },
+            Event::SubmitPassword => {
+                if self.new != self.confirm {
+                    msg = Some(Message::Error("Passwords do not match".to_string()));
+                } else {
+                    let request = self.request.clone();
+
+                    let (current, new) = (self.current.clone(), self.new.clone());
+                    let (token, address, username) =
+                        (self.token, self.address.clone(), self.username.clone());
+
+                    commands.push(Command::perform(
+                        async move { request.change_password(current, new, token).await },
+                        move |i| match i {
+                            Ok(()) => Event::Super(Box::new(Message::LoggedIn(
+                                token, false, address, username,
+                            ))),
+                            Err(e) => Event::Super(Box::new(Message::Error(e))),
+                        },
+                    ));
+                }
+
+                self.current.clear();
+                self.new.clear();
+                self.confirm.clear();
+            }
+
+            Event::Nav(forwards) => {
+                let cmd = if forwards {
+                    text_input::focus(self.tab_nav.next())
+                } else {
+                    text_input::focus(self.tab_nav.back())
+                };
+
+                commands.push(cmd);
+            }
+        }
+
+        (msg, Command::batch(commands))
+    }
+
+    pub fn view<'a>(&self) -> Element<'a, Event> {
+        let logout_icon = Image::new(Handle::from_memory(LOGOUT_BUTTON));
+
+        let logout_button =
+            icon_button(logout_icon).on_press(Event::Super(Box::new(Message::Logout)));
+
+        let nav = navbar(logout_button.into());
+
+        let title = Text::new(format!("Change the password for {}", self.username)).size(30);
+
+        let hint = Text::new("You must choose a new password before continuing.")
+            .size(20)
+            .style(theme::Text::Hint);
+
+        let input = |placeholder, value, field, id| {
+            text_input(placeholder, value)
+                .on_input(move |s| Event::UpdatePasswordInput(field, s))
+                .on_submit(Event::SubmitPassword)
+                .password()
+                .padding([10, 25])
+                .size(24.0)
+                .id(text_input::Id::new(id))
+        };
+
+        let current_input = input(
+            "Current password",
+            &self.current,
+            PasswordField::Current,
+            "current",
+        );
+        let new_input = input("New password", &self.new, PasswordField::New, "new");
+        let confirm_input = input(
+            "Confirm password",
+            &self.confirm,
+            PasswordField::Confirm,
+            "confirm",
+        );
+
+        let submit_button = button(Text::new("Change Password").size(24))
+            .on_press(Event::SubmitPassword)
+            .padding([10, 25]);
+
+        column!(
+            nav,
+            Space::new(Length::Fill, 50.0),
+            title,
+            hint,
+            Space::new(Length::Fill, 50.0),
+            column!(current_input, new_input, confirm_input)
+                .spacing(10)
+                .padding([0, 60]),
+            Space::new(Length::Fill, 25.0),
+            submit_button,
+        )
+        .height(Length::Fill)
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .into()
+    }
+}
@@ -1,10 +1,14 @@
+use chrono::{DateTime, Local};
 use iced::{
     widget::{self, image::Handle, *},
-    Length, Pixels,
+    Alignment, Command, Length, Pixels,
 };
+use models::{AuditEntry, CreateUserRequest, Permissions, Scope, UpdateUserRequest, UserInfo};
+use uuid::Uuid;
 
 use crate::{
     components::{icon_button, settings_card, tab_bar, Tab},
+    request::Request,
     settings::Settings,
     theme, Element, Message, SettingsField, BACK_ARROW,
 };
@@ -14,6 +18,7 @@ pub enum Page {
     #[default]
     Client,
     Server,
+    Audit,
 }
 
 impl Page {
@@ -24,10 +29,101 @@ impl Page {
     fn is_server(&self) -> bool {
         *self == Page::Server
     }
+
+    fn is_audit(&self) -> bool {
+        *self == Page::Audit
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserField {
+    UserId,
+    Password,
+    View,
+    Control,
+    Edit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditField {
+    User,
+    Action,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct UserForm {
+    user_id: String,
+    password: String,
+    admin: bool,
+    must_change_password: bool,
+
+    view: String,
+    control: String,
+    edit: String,
+}
+
+impl UserForm {
+    fn set(&mut self, field: UserField, value: String) {
+        match field {
+            UserField::UserId => self.user_id = value,
+            UserField::Password => self.password = value,
+            UserField::View => self.view = value,
+            UserField::Control => self.control = value,
+            UserField::Edit => self.edit = value,
+        }
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions {
+            admin: self.admin,
+
+            edit: parse_scope(&self.edit),
+            view: parse_scope(&self.view),
+            control: parse_scope(&self.control),
+        }
+    }
+}
+
+impl From<UserInfo> for UserForm {
+    fn from(value: UserInfo) -> Self {
+        Self {
+            user_id: value.user_id,
+            password: String::new(),
+            admin: value.permissions.admin,
+            must_change_password: value.must_change_password,
+
+            view: format_scope(&value.permissions.view),
+            control: format_scope(&value.permissions.control),
+            edit: format_scope(&value.permissions.edit),
+        }
+    }
+}
+
+fn parse_scope(value: &str) -> Scope {
+    if value.trim() == "*" {
+        return Scope::All;
+    }
+
+    Scope::Some(
+        value
+            .split(',')
+            .map(|i| i.trim().to_string())
+            .filter(|i| !i.is_empty())
+            .collect(),
+    )
+}
+
+fn format_scope(scope: &Scope) -> String {
+    match scope {
+        Scope::All => "*".to_string(),
+        Scope::Some(i) => i.join(", "),
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Event {
+    Super(Box<Message>),
+
     GotoPrevious,
 
     GotoPage(Page),
@@ -36,31 +132,288 @@ pub enum Event {
 
     Set(bool),
     SetDarkMode(bool),
+
+    RefreshUsers,
+    UsersLoaded(Vec<UserInfo>),
+    UpdateUserInput(Option<usize>, UserField, String),
+    SetAdmin(Option<usize>, bool),
+    SaveUser(usize),
+    DeleteUser(usize),
+    CreateUser,
+
+    RefreshAudit,
+    AuditLoaded(Vec<AuditEntry>),
+    UpdateAuditFilter(AuditField, String),
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct SettingsState {
     page: Page,
+
+    session: Option<(Request, Uuid)>,
+    users: Vec<UserForm>,
+    new_user: UserForm,
+
+    audit: Vec<AuditEntry>,
+    audit_user: String,
+    audit_action: String,
 }
 
 impl SettingsState {
-    pub fn update(&mut self, evt: Event) -> Option<Message> {
+    pub fn with_session(request: Request, token: Uuid) -> Self {
+        Self {
+            session: Some((request, token)),
+            ..Default::default()
+        }
+    }
+
+    fn form(&mut self, index: Option<usize>) -> Option<&mut UserForm> {
+        match index {
+            Some(i) => self.users.get_mut(i),
+            None => Some(&mut self.new_user),
+        }
+    }
+
+    pub fn update(&mut self, evt: Event) -> (Option<Message>, Command<Event>) {
         let mut msg = None;
+        let mut commands = vec![];
 
         match evt {
+            Event::Super(m) => msg = Some(*m),
+
             Event::GotoPrevious => msg = Some(Message::GotoPrevious),
 
-            Event::GotoPage(s) => self.page = s,
+            Event::GotoPage(s) => {
+                self.page = s;
+
+                if self.page.is_server() {
+                    return self.update(Event::RefreshUsers);
+                }
+
+                if self.page.is_audit() {
+                    return self.update(Event::RefreshAudit);
+                }
+            }
 
             Event::SetCache(v) => msg = Some(Message::UpdateSettings(SettingsField::Cache(v))),
             Event::SetDarkMode(v) => {
                 msg = Some(Message::UpdateSettings(SettingsField::DarkMode(v)))
             }
 
+            Event::RefreshUsers => {
+                let Some((request, token)) = self.session.clone() else {
+                    return (msg, Command::batch(commands));
+                };
+
+                commands.push(Command::perform(
+                    async move { request.list_users(token).await },
+                    |i| match i {
+                        Ok(users) => Event::UsersLoaded(users),
+                        Err(e) => Event::Super(Box::new(Message::Error(e))),
+                    },
+                ));
+            }
+            Event::UsersLoaded(users) => {
+                self.users = users.into_iter().map(UserForm::from).collect();
+            }
+            Event::UpdateUserInput(index, field, value) => {
+                if let Some(form) = self.form(index) {
+                    form.set(field, value);
+                }
+            }
+            Event::SetAdmin(index, admin) => {
+                if let Some(form) = self.form(index) {
+                    form.admin = admin;
+                }
+            }
+            Event::SaveUser(index) => {
+                let (Some((request, token)), Some(form)) =
+                    (self.session.clone(), self.users.get_mut(index))
+                else {
+                    return (msg, Command::batch(commands));
+                };
+
+                let user_id = form.user_id.clone();
+
+                let update = UpdateUserRequest {
+                    permissions: Some(form.permissions()),
+                    password: Some(std::mem::take(&mut form.password)).filter(|i| !i.is_empty()),
+                };
+
+                commands.push(Command::perform(
+                    async move { request.update_user(user_id, update, token).await },
+                    users_changed,
+                ));
+            }
+            Event::DeleteUser(index) => {
+                let (Some((request, token)), Some(form)) =
+                    (self.session.clone(), self.users.get(index))
+                else {
+                    return (msg, Command::batch(commands));
+                };
+
+                let user_id = form.user_id.clone();
+
+                commands.push(Command::perform(
+                    async move { request.delete_user(user_id, token).await },
+                    users_changed,
+                ));
+            }
+            Event::CreateUser => {
+                let Some((request, token)) = self.session.clone() else {
+                    return (msg, Command::batch(commands));
+                };
+
+                let form = std::mem::take(&mut self.new_user);
+
+                let user = CreateUserRequest {
+                    user_id: form.user_id.clone(),
+                    password: form.password.clone(),
+                    permissions: form.permissions(),
+                };
+
+                commands.push(Command::perform(
+                    async move { request.create_user(user, token).await },
+                    users_changed,
+                ));
+            }
+
+            Event::RefreshAudit => {
+                let Some((request, token)) = self.session.clone() else {
+                    return (msg, Command::batch(commands));
+                };
+
+                let user = Some(self.audit_user.trim().to_string()).filter(|i| !i.is_empty());
+                let action = Some(self.audit_action.trim().to_string()).filter(|i| !i.is_empty());
+
+                commands.push(Command::perform(
+                    async move { request.get_audit(user, action, token).await },
+                    |i| match i {
+                        Ok(entries) => Event::AuditLoaded(entries),
+                        Err(e) => Event::Super(Box::new(Message::Error(e))),
+                    },
+                ));
+            }
+            Event::AuditLoaded(entries) => self.audit = entries,
+            Event::UpdateAuditFilter(field, value) => match field {
+                AuditField::User => self.audit_user = value,
+                AuditField::Action => self.audit_action = value,
+            },
+
             _ => {}
         }
 
-        msg
+        (msg, Command::batch(commands))
+    }
+
+    fn users_view<'a>(&self) -> Element<'a, Event> {
+        if self.session.is_none() {
+            return Text::new("Log in to manage users.")
+                .size(20)
+                .style(theme::Text::Hint)
+                .into();
+        }
+
+        let mut col = widget::column!().spacing(25);
+
+        for (index, user) in self.users.iter().enumerate() {
+            let mut name = user.user_id.clone();
+
+            if user.must_change_password {
+                name.push_str(" (password change pending)");
+            }
+
+            let header = row!(
+                Text::new(name).size(30),
+                Space::new(Length::Fill, 0.0),
+                Text::new("Admin").size(20),
+                Toggler::new(None, user.admin, move |v| Event::SetAdmin(Some(index), v))
+                    .width(64)
+                    .size(32),
+                button(Text::new("Save").size(20))
+                    .on_press(Event::SaveUser(index))
+                    .padding([5, 15]),
+                button(Text::new("Delete").size(20))
+                    .on_press(Event::DeleteUser(index))
+                    .style(theme::Button::Destructive)
+                    .padding([5, 15])
+            )
+            .spacing(15)
+            .align_items(Alignment::Center);
+
+            let fields = user_fields(Some(index), user);
+
+            col = col.push(widget::column!(header, fields, Rule::horizontal(1)).spacing(10));
+        }
+
+        let create_header = row!(
+            Text::new("New User").size(30),
+            Space::new(Length::Fill, 0.0),
+            Text::new("Admin").size(20),
+            Toggler::new(None, self.new_user.admin, |v| Event::SetAdmin(None, v))
+                .width(64)
+                .size(32),
+            button(Text::new("Create").size(20))
+                .on_press(Event::CreateUser)
+                .padding([5, 15])
+        )
+        .spacing(15)
+        .align_items(Alignment::Center);
+
+        let username = text_input("Username", &self.new_user.user_id)
+            .on_input(|s| Event::UpdateUserInput(None, UserField::UserId, s))
+            .padding([5, 15])
+            .size(20);
+
+        let fields = user_fields(None, &self.new_user);
+
+        col = col.push(widget::column!(create_header, username, fields).spacing(10));
+
+        scrollable(col.padding([0, 20])).height(Length::Fill).into()
+    }
+
+    fn audit_view<'a>(&self) -> Element<'a, Event> {
+        if self.session.is_none() {
+            return Text::new("Log in to view the audit log.")
+                .size(20)
+                .style(theme::Text::Hint)
+                .into();
+        }
+
+        let filters = row!(
+            text_input("User", &self.audit_user)
+                .on_input(|s| Event::UpdateAuditFilter(AuditField::User, s))
+                .on_submit(Event::RefreshAudit)
+                .padding([5, 15])
+                .size(20),
+            text_input("Action", &self.audit_action)
+                .on_input(|s| Event::UpdateAuditFilter(AuditField::Action, s))
+                .on_submit(Event::RefreshAudit)
+                .padding([5, 15])
+                .size(20),
+            button(Text::new("Refresh").size(20))
+                .on_press(Event::RefreshAudit)
+                .padding([5, 15])
+        )
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let mut col = widget::column!().spacing(5);
+
+        if self.audit.is_empty() {
+            col = col.push(Text::new("No entries").size(20).style(theme::Text::Hint));
+        }
+
+        for entry in self.audit.iter().rev() {
+            col = col.push(audit_row(entry));
+        }
+
+        widget::column!(
+            filters,
+            scrollable(col.padding([0, 20])).height(Length::Fill)
+        )
+        .spacing(25)
+        .into()
     }
 
     pub fn view<'a>(&self, settings: &Settings) -> Element<'a, Event> {
@@ -74,6 +427,9 @@ impl SettingsState {
             Tab::new("Server")
                 .selected(self.page.is_server())
                 .on_select(Event::GotoPage(Page::Server)),
+            Tab::new("Audit")
+                .selected(self.page.is_audit())
+                .on_select(Event::GotoPage(Page::Audit)),
         ]);
 
         let width = iced::advanced::Widget::width(&back_button);
@@ -105,8 +461,83 @@ impl SettingsState {
                 .size(32),
         );
 
-        let settings = widget::column!(dark_mode, cache).spacing(25).padding([0, 120]);
+        let settings: Element<'a, Event> = match self.page {
+            Page::Client => widget::column!(dark_mode, cache).spacing(25).into(),
+            Page::Server => self.users_view(),
+            Page::Audit => self.audit_view(),
+        };
+
+        widget::column!(nav_row, Container::new(settings).padding([0, 120])).into()
+    }
+}
+
+fn users_changed(result: Result<(), String>) -> Event {
+    match result {
+        Ok(()) => Event::RefreshUsers,
+        Err(e) => Event::Super(Box::new(Message::Error(e))),
+    }
+}
+
+fn audit_row<'a>(entry: &AuditEntry) -> Element<'a, Event> {
+    let time = DateTime::from_timestamp(entry.timestamp as i64, 0)
+        .map(|i| {
+            i.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    let mut summary = format!(
+        "{} {} {}",
+        entry.user.as_deref().unwrap_or("-"),
+        entry.action,
+        entry.target.as_deref().unwrap_or("")
+    );
+
+    if let Some(detail) = &entry.detail {
+        summary.push_str(&format!(" ({})", detail));
+    }
+
+    let mut summary = Text::new(summary).size(20);
 
-        widget::column!(nav_row, settings).into()
+    if !entry.success {
+        summary = summary.style(theme::Text::Destructive);
     }
+
+    row!(
+        Text::new(time).size(20).style(theme::Text::Hint),
+        summary,
+        Space::new(Length::Fill, 0.0),
+        Text::new(entry.ip.clone())
+            .size(20)
+            .style(theme::Text::Hint)
+    )
+    .spacing(20)
+    .into()
+}
+
+fn user_fields<'a>(index: Option<usize>, user: &UserForm) -> Element<'a, Event> {
+    let input = |placeholder: &str, value: &str, field| {
+        text_input(placeholder, value)
+            .on_input(move |s| Event::UpdateUserInput(index, field, s))
+            .padding([5, 15])
+            .size(20)
+    };
+
+    let password = match index {
+        Some(_) => "Reset password",
+        None => "Password",
+    };
+
+    widget::column!(
+        input(password, &user.password, UserField::Password).password(),
+        row!(
+            input("View scope (ids or *)", &user.view, UserField::View),
+            input("Control scope", &user.control, UserField::Control),
+            input("Edit scope", &user.edit, UserField::Edit)
+        )
+        .spacing(10)
+    )
+    .spacing(10)
+    .into()
 }
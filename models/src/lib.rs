@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use uuid::Uuid;
 
@@ -26,6 +28,40 @@ impl <T> FromJson for T where T: DeserializeOwned {
 pub struct ServerStatus {
     pub id: String,
     pub running: bool,
+    pub scrollback: usize,
+    pub restarts: u32,
+    pub last_exit_code: Option<i32>,
+    pub last_exit_signal: Option<i32>,
+    pub last_exit_reason: Option<String>,
+    pub started_at: Option<u64>,
+    pub stopped_at: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MetricSample {
+    pub timestamp: u64,
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub processes: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ServerMetrics {
+    pub id: String,
+    pub uptime: Option<u64>,
+    pub samples: Vec<MetricSample>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SystemOverview {
+    pub load_average: Option<[f32; 3]>,
+    pub memory_total: Option<u64>,
+    pub memory_available: Option<u64>,
+    pub disk_total: Option<u64>,
+    pub disk_available: Option<u64>,
+    pub uptime: u64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -33,9 +69,40 @@ pub struct GlobalStatus {
     pub servers: Vec<ServerStatus>,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdin,
+    Stdout,
+    Stderr,
+    Panel,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ConsoleLine {
+    pub seq: u64,
+    pub stream: Stream,
+    pub content: String,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ServerOutput {
-    pub output: Option<Vec<String>>,
+    pub output: Option<Vec<ConsoleLine>>,
+
+    #[serde(default)]
+    pub reset: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "event", content = "data", rename_all = "lowercase")]
+pub enum ServerEvent {
+    Status(ServerStatus),
+    Output(ServerOutput),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ErrorResponse {
+    pub error: String,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -52,4 +119,169 @@ pub struct TokenRequest {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct TokenResponse {
     pub token: Option<Uuid>,
+    #[serde(default)]
+    pub must_change_password: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PasswordChangeRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Scope {
+    All,
+    Some(Vec<String>),
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::Some(vec![])
+    }
+}
+
+impl Scope {
+    pub fn contains(&self, other: &String) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Some(i) => i.contains(other),
+        }
+    }
+
+    pub fn intersect(&self, other: &Scope) -> Scope {
+        match (self, other) {
+            (Scope::All, other) => other.clone(),
+            (this, Scope::All) => this.clone(),
+            (Scope::Some(a), Scope::Some(b)) => {
+                Scope::Some(a.iter().filter(|i| b.contains(i)).cloned().collect())
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Permissions {
+    pub admin: bool,
+
+    pub edit: Scope,
+    pub view: Scope,
+    pub control: Scope,
+}
+
+impl Permissions {
+    pub fn intersect(&self, other: &Permissions) -> Permissions {
+        Permissions {
+            admin: self.admin && other.admin,
+
+            edit: self.edit.intersect(&other.edit),
+            view: self.view.intersect(&other.view),
+            control: self.control.intersect(&other.control),
+        }
+    }
+
+    pub fn admin() -> Self {
+        Self {
+            admin: true,
+
+            edit: Scope::All,
+            view: Scope::All,
+            control: Scope::All,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UserInfo {
+    pub user_id: String,
+    pub permissions: Permissions,
+    pub must_change_password: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CreateUserRequest {
+    pub user_id: String,
+    pub password: String,
+    pub permissions: Permissions,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UpdateUserRequest {
+    pub permissions: Option<Permissions>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ServerDefinition {
+    pub id: String,
+    pub display: String,
+    pub start_command: String,
+
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ServerUpdate {
+    pub display: Option<String>,
+    pub start_command: Option<String>,
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub permissions: Permissions,
+    pub created: u64,
+    pub expires: Option<u64>,
+    pub last_used: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CreateTokenRequest {
+    pub user_id: String,
+    pub name: String,
+    pub permissions: Permissions,
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CreateTokenResponse {
+    pub id: String,
+    pub token: Uuid,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LockoutKind {
+    User,
+    Ip,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LockoutInfo {
+    pub kind: LockoutKind,
+    pub key: String,
+    pub failures: u32,
+    pub remaining: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ClearLockoutRequest {
+    pub kind: LockoutKind,
+    pub key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub user: Option<String>,
+    pub ip: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub detail: Option<String>,
+    pub success: bool,
 }
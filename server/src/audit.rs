@@ -0,0 +1,350 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use foxhole::{
+    resolve::{Resolve, ResolveGuard},
+    type_cache::TypeCacheKey,
+    PathIter, RequestState,
+};
+use models::{AuditEntry, FromJson, ToJson};
+
+use crate::{authentication, client_ip::ClientIp, params::Params, unix_time};
+
+const DEFAULT_LIMIT: usize = 200;
+
+/// Once the log reaches this size it is renamed to the next unused
+/// `audit.<n>.jsonl`. Rotated logs are never deleted by the panel.
+const MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+pub struct AuditLog {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl TypeCacheKey for AuditLog {
+    type Value = Arc<RwLock<AuditLog>>;
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        let path = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("audit.jsonl");
+
+        Self { path, file: None }
+    }
+
+    pub fn record(&mut self, entry: AuditEntry) {
+        if let Err(e) = self.append(&entry) {
+            println!("Failed to write audit log {:?}: {e}", self.path);
+
+            self.file = None;
+        }
+    }
+
+    fn append(&mut self, entry: &AuditEntry) -> io::Result<()> {
+        if self.file.is_none() {
+            self.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+
+        let file = self.file.as_mut().unwrap();
+
+        writeln!(file, "{}", entry.to_json())?;
+
+        if file.metadata()?.len() >= MAX_SIZE {
+            self.rotate()?;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+
+        let next = self.rotations().first().map_or(1, |i| i + 1);
+
+        fs::rename(&self.path, self.rotated(next))
+    }
+
+    fn rotated(&self, number: u64) -> PathBuf {
+        self.path.with_extension(format!("{}.jsonl", number))
+    }
+
+    /// The numbers of the rotated logs, newest first.
+    fn rotations(&self) -> Vec<u64> {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+
+        let mut numbers: Vec<u64> = self
+            .path
+            .parent()
+            .and_then(|i| fs::read_dir(i).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|i| {
+                let name = i.ok()?.file_name().into_string().ok()?;
+
+                name.strip_prefix(&*stem)?
+                    .strip_prefix('.')?
+                    .strip_suffix(".jsonl")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+
+        numbers.sort_unstable_by(|a, b| b.cmp(a));
+
+        numbers
+    }
+
+    pub fn query(&self, params: &Params) -> Vec<AuditEntry> {
+        let user = params.get::<String>("user");
+        let action = params.get::<String>("action");
+        let target = params.get::<String>("target");
+        let since = params.get::<u64>("since");
+        let until = params.get::<u64>("until");
+        let limit = params.get::<usize>("limit").unwrap_or(DEFAULT_LIMIT);
+
+        let rotated = self.rotations().into_iter().map(|i| self.rotated(i));
+
+        let mut entries: Vec<AuditEntry> = std::iter::once(self.path.clone())
+            .chain(rotated)
+            .flat_map(|i| RevLines::open(&i))
+            .flatten()
+            .filter_map(AuditEntry::from_json)
+            .take_while(|i| i.timestamp >= since.unwrap_or(0))
+            .filter(|i| user.is_none() || i.user == user)
+            .filter(|i| action.is_none() || action.as_ref() == Some(&i.action))
+            .filter(|i| target.is_none() || i.target == target)
+            .filter(|i| i.timestamp <= until.unwrap_or(u64::MAX))
+            .take(limit)
+            .collect();
+
+        entries.reverse();
+
+        entries
+    }
+}
+
+/// The lines of a file from last to first, read in chunks from the end.
+struct RevLines {
+    file: File,
+    pos: u64,
+    pending: Vec<u8>,
+    lines: Vec<String>,
+}
+
+impl RevLines {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        let pos = file.metadata()?.len();
+
+        Ok(Self {
+            file,
+            pos,
+            pending: vec![],
+            lines: vec![],
+        })
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let size = self.pos.min(CHUNK_SIZE);
+
+        self.pos -= size;
+
+        let mut chunk = vec![0; size as usize];
+
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        self.file.read_exact(&mut chunk)?;
+
+        chunk.append(&mut self.pending);
+
+        let mut parts = chunk.split(|i| *i == b'\n');
+
+        // The first part may continue in the previous chunk.
+        if self.pos > 0 {
+            self.pending = parts.next().unwrap_or_default().to_vec();
+        }
+
+        self.lines.extend(
+            parts
+                .filter(|i| !i.is_empty())
+                .map(|i| String::from_utf8_lossy(i).to_string()),
+        );
+
+        Ok(())
+    }
+}
+
+impl Iterator for RevLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while self.lines.is_empty() && self.pos > 0 {
+            if self.fill().is_err() {
+                return None;
+            }
+        }
+
+        self.lines.pop()
+    }
+}
+
+pub struct Audit {
+    user: Option<String>,
+    ip: String,
+    log: Arc<RwLock<AuditLog>>,
+}
+
+impl Audit {
+    pub fn record(
+        &self,
+        action: &str,
+        target: Option<&str>,
+        detail: Option<String>,
+        success: bool,
+    ) {
+        self.record_as(self.user.clone(), action, target, detail, success);
+    }
+
+    pub fn record_as(
+        &self,
+        user: Option<String>,
+        action: &str,
+        target: Option<&str>,
+        detail: Option<String>,
+        success: bool,
+    ) {
+        self.log.write().unwrap().record(AuditEntry {
+            timestamp: unix_time(SystemTime::now()),
+            user,
+            ip: self.ip.clone(),
+            action: action.to_string(),
+            target: target.map(|i| i.to_string()),
+            detail,
+            success,
+        });
+    }
+}
+
+impl<'a> Resolve<'a> for Audit {
+    type Output = Audit;
+
+    fn resolve(ctx: &'a RequestState, path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        let ip = match ClientIp::resolve(ctx, path_iter) {
+            ResolveGuard::Value(ClientIp(ip)) => ip,
+            ResolveGuard::Respond(response) => return ResolveGuard::Respond(response),
+            ResolveGuard::None => return ResolveGuard::None,
+        };
+
+        let user = authentication::current_user(ctx);
+
+        let log = ctx
+            .global_cache
+            .read()
+            .unwrap()
+            .get::<AuditLog>()
+            .unwrap()
+            .clone();
+
+        ResolveGuard::Value(Audit { user, ip, log })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn log() -> AuditLog {
+        let dir = std::env::temp_dir().join(format!("kitpanel-test-{}", uuid::Uuid::new_v4()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        AuditLog {
+            path: dir.join("audit.jsonl"),
+            file: None,
+        }
+    }
+
+    fn entry(timestamp: u64, action: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            user: Some("admin".to_string()),
+            ip: "127.0.0.1".to_string(),
+            action: action.to_string(),
+            target: None,
+            detail: None,
+            success: true,
+        }
+    }
+
+    fn query(log: &AuditLog, params: &[(&str, &str)]) -> Vec<AuditEntry> {
+        let params: HashMap<_, _> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        log.query(&Params(params))
+    }
+
+    #[test]
+    fn reads_lines_backwards_across_chunks() {
+        let log = log();
+
+        let lines: Vec<String> = (0..20000).map(|i| format!("line {}", i)).collect();
+
+        fs::write(&log.path, lines.join("\n") + "\n").unwrap();
+
+        assert!(fs::metadata(&log.path).unwrap().len() > 2 * CHUNK_SIZE);
+
+        let read: Vec<String> = RevLines::open(&log.path).unwrap().collect();
+
+        assert!(read.iter().rev().eq(lines.iter()));
+
+        fs::remove_dir_all(log.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn queries_rotated_logs_newest_first() {
+        let mut log = log();
+
+        for timestamp in 1..=9 {
+            log.record(entry(timestamp, "input"));
+
+            if timestamp % 3 == 0 {
+                log.rotate().unwrap();
+            }
+        }
+
+        assert_eq!(log.rotations(), [3, 2, 1]);
+
+        let timestamps = |entries: Vec<AuditEntry>| -> Vec<u64> {
+            entries.iter().map(|i| i.timestamp).collect()
+        };
+
+        assert_eq!(timestamps(query(&log, &[])), (1..=9).collect::<Vec<_>>());
+        assert_eq!(timestamps(query(&log, &[("limit", "4")])), [6, 7, 8, 9]);
+        assert_eq!(
+            timestamps(query(&log, &[("since", "5"), ("until", "7")])),
+            [5, 6, 7]
+        );
+
+        fs::remove_dir_all(log.path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,113 +1,259 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-    time::{Duration, Instant},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    time::{Duration, Instant, SystemTime},
 };
 
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
 use foxhole::{
+    action::RawResponse,
+    http::Version,
     resolve::{Resolve, ResolveGuard},
     type_cache::TypeCacheKey,
-    IntoResponse, PathIter, RequestState,
+    IntoResponse, PathIter, RequestState, Response,
 };
+use models::{ApiTokenInfo, Permissions, Scope, UserInfo};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::{fs::Config, SESSION_LENGTH};
+use crate::{
+    fs::Config, proxy, server_config::ServerConfig, unix_time, SESSION_IDLE_TIMEOUT, SESSION_LENGTH,
+};
 
 pub type UserId = String;
 pub type Password = String;
 pub type Token = Uuid;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-#[serde(untagged)]
-pub enum Scope {
-    All,
-    Some(Vec<String>),
+pub const PASSWORD_CHANGE_REQUIRED: u16 = 428;
+
+const DEFAULT_PASSWORD: &str = "password";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub user_id: UserId,
+    pub permissions: Permissions,
+
+    #[serde(default)]
+    pub password_hash: String,
+
+    /// Plaintext password of accounts saved before passwords were hashed.
+    #[serde(default, rename = "password", skip_serializing)]
+    legacy_password: Option<String>,
+
+    #[serde(default)]
+    pub must_change_password: bool,
+
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
 }
 
-impl Default for Scope {
-    fn default() -> Self {
-        Self::Some(vec![])
-    }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub permissions: Permissions,
+    pub hash: String,
+    pub created: u64,
+    pub expires: Option<u64>,
+    pub last_used: Option<u64>,
 }
 
-impl Scope {
-    pub fn contains(&self, other: &String) -> bool {
-        match self {
-            Scope::All => true,
-            Scope::Some(i) => i.contains(other),
+impl ApiToken {
+    pub fn new(name: String, permissions: Permissions, expires: Option<u64>) -> (Self, Token) {
+        let token = Uuid::new_v4();
+
+        let api_token = Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            permissions,
+            hash: hash_token(&token),
+            created: unix_time(SystemTime::now()),
+            expires,
+            last_used: None,
+        };
+
+        (api_token, token)
+    }
+
+    pub fn info(&self, user_id: &UserId) -> ApiTokenInfo {
+        ApiTokenInfo {
+            id: self.id.clone(),
+            user_id: user_id.clone(),
+            name: self.name.clone(),
+            permissions: self.permissions.clone(),
+            created: self.created,
+            expires: self.expires,
+            last_used: self.last_used,
         }
     }
+
+    fn expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|i| now >= i)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
-pub struct Permissions {
-    admin: bool,
+impl User {
+    pub fn new(user_id: UserId, password: &Password, permissions: Permissions) -> Self {
+        Self {
+            user_id,
+            permissions,
+            password_hash: hash_password(password),
+            legacy_password: None,
+            must_change_password: true,
+            tokens: vec![],
+        }
+    }
 
-    edit: Scope,
-    view: Scope,
-    control: Scope,
+    pub fn info(&self) -> UserInfo {
+        UserInfo {
+            user_id: self.user_id.clone(),
+            permissions: self.permissions.clone(),
+            must_change_password: self.must_change_password,
+        }
+    }
 }
 
-impl Permissions {
-    fn admin() -> Self {
-        Self::default()
+impl<'a> Resolve<'a> for User {
+    type Output = User;
+
+    fn resolve(ctx: &'a RequestState, _path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        match resolve_user(ctx) {
+            Ok(user) if user.must_change_password => {
+                ResolveGuard::Respond(PASSWORD_CHANGE_REQUIRED.response())
+            }
+            Ok(user) => ResolveGuard::Value(user),
+            Err(status) => ResolveGuard::Respond(reject(status)),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct User {
-    pub user_id: UserId,
-    pub permissions: Permissions,
-    pub password: String,
+pub struct Account(pub User);
+
+impl<'a> Resolve<'a> for Account {
+    type Output = Account;
+
+    fn resolve(ctx: &'a RequestState, _path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        match resolve_user(ctx) {
+            Ok(user) => ResolveGuard::Value(Account(user)),
+            Err(status) => ResolveGuard::Respond(reject(status)),
+        }
+    }
 }
 
-impl<'a> Resolve<'a> for User {
-    type Output = User;
+pub struct SessionToken(pub Token);
+
+impl<'a> Resolve<'a> for SessionToken {
+    type Output = SessionToken;
 
     fn resolve(ctx: &'a RequestState, _path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
-        let Some(Ok(token)) = ctx
-            .request
-            .headers()
-            .get("authorization")
-            .map(|i| i.to_str())
-        else {
-            return ResolveGuard::Respond(401u16.response());
-        };
+        match session_token(ctx) {
+            Some(token) => ResolveGuard::Value(SessionToken(token)),
+            None => ResolveGuard::Respond(unauthorized()),
+        }
+    }
+}
 
-        let Ok(token) = serde_json::from_str(token) else {
-            return ResolveGuard::Respond(401u16.response());
+/// Describes each access in `permissions` to a server not in `servers`.
+pub fn unknown_servers(permissions: &Permissions, servers: &HashSet<&str>) -> Vec<String> {
+    let mut unknown = vec![];
+
+    for (name, scope) in [
+        ("view", &permissions.view),
+        ("control", &permissions.control),
+        ("edit", &permissions.edit),
+    ] {
+        let Scope::Some(ids) = scope else {
+            continue;
         };
 
-        let cache = ctx.global_cache.read().unwrap();
+        for id in ids.iter().filter(|i| !servers.contains(i.as_str())) {
+            unknown.push(format!("{} access to unknown server {:?}", name, id));
+        }
+    }
+
+    unknown
+}
 
-        let auth = cache.get::<Authentication>().unwrap().read().unwrap();
+fn session_token(ctx: &RequestState) -> Option<Token> {
+    parse_token(ctx.request.headers().get("authorization")?.to_str().ok()?)
+}
 
-        let Some(session) = auth.sessions.get(&token) else {
-            return ResolveGuard::Respond(401u16.response());
-        };
+/// Accepts `Bearer <token>` as well as the JSON string older clients send.
+fn parse_token(header: &str) -> Option<Token> {
+    match header.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => {
+            Uuid::parse_str(token.trim()).ok()
+        }
+        _ => serde_json::from_str(header).ok(),
+    }
+}
 
-        let Some(user) = auth.users.get(&session.user_id) else {
-            return ResolveGuard::Respond(401u16.response());
-        };
+pub fn current_user(ctx: &RequestState) -> Option<UserId> {
+    resolve_user(ctx).ok().map(|i| i.user_id)
+}
+
+pub fn unauthorized() -> RawResponse {
+    Response::builder()
+        .version(Version::HTTP_11)
+        .status(401)
+        .header("www-authenticate", "Bearer realm=\"KitPanel\"")
+        .header("content-length", "0")
+        .body(vec![])
+        .expect("Failed to build response")
+}
+
+fn reject(status: u16) -> RawResponse {
+    match status {
+        401 => unauthorized(),
+        status => status.response(),
+    }
+}
+
+fn resolve_user(ctx: &RequestState) -> Result<User, u16> {
+    if proxy::peer(ctx).is_none() {
+        return Err(403);
+    }
 
-        ResolveGuard::Value(user.clone())
+    let Some(token) = session_token(ctx) else {
+        return Err(401);
+    };
+
+    let cache = ctx.global_cache.read().unwrap();
+
+    let auth = cache.get::<Authentication>().unwrap().read().unwrap();
+
+    if let Some(user_id) = auth.touch(&token) {
+        return auth.users.get(&user_id).cloned().ok_or(401);
     }
+
+    auth.use_api_token(&token).ok_or(401)
 }
 
 #[derive(Debug)]
 pub struct Session {
-    started: Instant,
+    expires: Instant,
+    last_active: Mutex<Instant>,
     user_id: UserId,
 }
 
 impl Session {
     fn new(user_id: UserId) -> Self {
+        let now = Instant::now();
+
         Self {
-            started: Instant::now(),
+            expires: now + SESSION_LENGTH,
+            last_active: Mutex::new(now),
             user_id,
         }
     }
+
+    fn expired(&self, now: Instant) -> bool {
+        now >= self.expires || now - *self.last_active.lock().unwrap() > SESSION_IDLE_TIMEOUT
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -116,6 +262,11 @@ pub struct Authentication {
 
     #[serde(skip_serializing, skip_deserializing)]
     pub sessions: HashMap<Token, Session>,
+
+    /// When API tokens were last used by id, written to the tokens by
+    /// `clean_auth` so requests only need a read lock.
+    #[serde(skip_serializing, skip_deserializing)]
+    token_usage: Mutex<HashMap<String, u64>>,
 }
 
 impl Default for Authentication {
@@ -133,8 +284,31 @@ impl Config for Authentication {
         serde_json::to_vec_pretty(self).unwrap()
     }
 
-    fn from_bytes(bytes: &[u8]) -> Option<Self> {
-        serde_json::from_slice(bytes).ok()
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+
+    fn migrate(&mut self) -> bool {
+        let mut changed = false;
+
+        for user in self.users.values_mut() {
+            if let Some(password) = user.legacy_password.take() {
+                let untouched = password == DEFAULT_PASSWORD;
+
+                // The old template saved its admin with every permission
+                // unset, which left it unable to do anything once
+                // permissions were enforced
+                if untouched && user.user_id == "admin" {
+                    user.permissions = Permissions::admin();
+                }
+
+                user.must_change_password |= untouched;
+                user.password_hash = hash_password(&password);
+                changed = true;
+            }
+        }
+
+        changed
     }
 }
 
@@ -147,6 +321,7 @@ impl Authentication {
         let mut authentication = Authentication {
             users: HashMap::new(),
             sessions: HashMap::new(),
+            token_usage: Mutex::default(),
         };
 
         authentication.users.insert(
@@ -154,15 +329,127 @@ impl Authentication {
             User {
                 user_id: "admin".to_string(),
                 permissions: Permissions::admin(),
-                password: "password".to_string(),
+                password_hash: hash_password(DEFAULT_PASSWORD),
+                legacy_password: None,
+                must_change_password: true,
+                tokens: vec![],
             },
         );
 
         authentication
     }
 
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+
+        for (key, user) in self.users.iter() {
+            if *key != user.user_id {
+                errors.push(format!(
+                    "User {:?} is stored under the key {:?}",
+                    user.user_id, key
+                ));
+            }
+
+            if user.password_hash.is_empty() && user.legacy_password.is_none() {
+                errors.push(format!("User {:?} has no password", key));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Access to servers that are not configured, left over from a removed
+    /// server or a typo, which grants nothing but should be cleaned up.
+    pub fn warnings(&self, config: &ServerConfig) -> Vec<String> {
+        let servers = config.ids();
+
+        let mut warnings = vec![];
+
+        for (key, user) in self.users.iter() {
+            let scopes = std::iter::once((None, &user.permissions)).chain(
+                user.tokens
+                    .iter()
+                    .map(|i| (Some(i.name.as_str()), &i.permissions)),
+            );
+
+            for (token, permissions) in scopes {
+                let owner = match token {
+                    Some(token) => format!("Token {:?} of user {:?}", token, key),
+                    None => format!("User {:?}", key),
+                };
+
+                for unknown in unknown_servers(permissions, &servers) {
+                    warnings.push(format!("{} has {}", owner, unknown));
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Removes a deleted server from every user's and token's scopes,
+    /// returning whether any access was removed.
+    pub fn forget_server(&mut self, server_id: &str) -> bool {
+        let mut changed = false;
+
+        for user in self.users.values_mut() {
+            let permissions = std::iter::once(&mut user.permissions)
+                .chain(user.tokens.iter_mut().map(|i| &mut i.permissions));
+
+            for permissions in permissions {
+                for scope in [
+                    &mut permissions.view,
+                    &mut permissions.control,
+                    &mut permissions.edit,
+                ] {
+                    if let Scope::Some(ids) = scope {
+                        let len = ids.len();
+
+                        ids.retain(|i| i != server_id);
+
+                        changed |= ids.len() != len;
+                    }
+                }
+            }
+        }
+
+        changed
+    }
+
     pub fn get_user(&self, user_id: &UserId, password: &Password) -> Option<&User> {
-        self.users.get(user_id).filter(|t| t.password == *password)
+        let Some(user) = self.users.get(user_id) else {
+            verify_password(dummy_hash(), password);
+
+            return None;
+        };
+
+        verify_password(&user.password_hash, password).then_some(user)
+    }
+
+    pub fn set_password(&mut self, user_id: &UserId, password: &Password) -> Option<()> {
+        let user = self.users.get_mut(user_id)?;
+
+        user.password_hash = hash_password(password);
+        user.must_change_password = false;
+
+        Some(())
+    }
+
+    pub fn remove_user(&mut self, user_id: &UserId) -> Option<User> {
+        let user = self.users.remove(user_id)?;
+
+        self.end_sessions(user_id, None);
+
+        Some(user)
+    }
+
+    pub fn end_sessions(&mut self, user_id: &UserId, except: Option<&Token>) {
+        self.sessions.retain(|token, session| {
+            session.user_id != *user_id || except.is_some_and(|i| i == token)
+        });
     }
 
     pub fn create_session(&mut self, user_id: &str) -> Token {
@@ -175,21 +462,171 @@ impl Authentication {
         token
     }
 
-    pub fn clean(&mut self, timeout: Duration) {
+    pub fn touch(&self, token: &Token) -> Option<UserId> {
+        let now = Instant::now();
+
+        let session = self.sessions.get(token)?;
+
+        if session.expired(now) {
+            return None;
+        }
+
+        *session.last_active.lock().unwrap() = now;
+
+        Some(session.user_id.clone())
+    }
+
+    pub fn refresh(&mut self, token: &Token) -> Option<UserId> {
+        let user_id = self.touch(token)?;
+
+        let session = self.sessions.get_mut(token)?;
+
+        session.expires = Instant::now() + SESSION_LENGTH;
+
+        Some(user_id)
+    }
+
+    pub fn revoke(&mut self, token: &Token) -> bool {
+        self.sessions.remove(token).is_some()
+    }
+
+    pub fn carry_over(&mut self, previous: &mut Authentication) {
+        self.sessions = std::mem::take(&mut previous.sessions);
+        self.sessions
+            .retain(|_, session| self.users.contains_key(&session.user_id));
+
+        for user in self.users.values_mut() {
+            let Some(old) = previous.users.get(&user.user_id) else {
+                continue;
+            };
+
+            for token in user.tokens.iter_mut() {
+                if let Some(old) = old.tokens.iter().find(|i| i.id == token.id) {
+                    token.last_used = token.last_used.max(old.last_used);
+                }
+            }
+        }
+
+        self.token_usage = std::mem::take(&mut previous.token_usage);
+    }
+
+    pub fn use_api_token(&self, token: &Token) -> Option<User> {
+        let hash = hash_token(token);
+        let now = unix_time(SystemTime::now());
+
+        let user = self
+            .users
+            .values()
+            .find(|i| i.tokens.iter().any(|t| t.hash == hash))?;
+
+        let api_token = user.tokens.iter().find(|t| t.hash == hash)?;
+
+        if api_token.expired(now) {
+            return None;
+        }
+
+        self.token_usage
+            .lock()
+            .unwrap()
+            .insert(api_token.id.clone(), now);
+
+        let permissions = user.permissions.intersect(&api_token.permissions);
+
+        Some(User {
+            permissions,
+            ..user.clone()
+        })
+    }
+
+    pub fn revoke_api_token(&mut self, id: &str) -> bool {
+        for user in self.users.values_mut() {
+            let before = user.tokens.len();
+
+            user.tokens.retain(|i| i.id != id);
+
+            if user.tokens.len() != before {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn tokens(&self) -> Vec<ApiTokenInfo> {
+        let usage = self.token_usage.lock().unwrap();
+
+        self.users
+            .values()
+            .flat_map(|user| {
+                user.tokens.iter().map(|i| ApiTokenInfo {
+                    last_used: usage.get(&i.id).copied().or(i.last_used),
+                    ..i.info(&user.user_id)
+                })
+            })
+            .collect()
+    }
+
+    fn flush_token_usage(&mut self) -> bool {
+        let usage = std::mem::take(self.token_usage.get_mut().unwrap());
+
+        for token in self.users.values_mut().flat_map(|i| i.tokens.iter_mut()) {
+            if let Some(used) = usage.get(&token.id) {
+                token.last_used = Some(*used);
+            }
+        }
+
+        !usage.is_empty()
+    }
+
+    pub fn clean(&mut self) {
         let now = Instant::now();
 
-        self.sessions = std::mem::take(&mut self.sessions)
-            .into_iter()
-            .filter(|session| (now - session.1.started) <= timeout)
-            .collect();
+        self.sessions.retain(|_, session| !session.expired(now));
     }
 }
 
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Failed to hash password")
+        .to_string()
+}
+
+/// Verified against for unknown users, so a login takes as long whether or
+/// not the user exists.
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+
+    DUMMY.get_or_init(|| hash_password(DEFAULT_PASSWORD))
+}
+
+fn hash_token(token: &Token) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    let Ok(hash) = PasswordHash::new(hash) else {
+        return false;
+    };
+
+    Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok()
+}
+
 pub fn clean_auth(auth: Arc<RwLock<Authentication>>) {
     loop {
         std::thread::sleep(Duration::from_secs(120));
 
-        auth.write().unwrap().clean(SESSION_LENGTH);
+        let mut auth = auth.write().unwrap();
+
+        auth.clean();
+
+        if auth.flush_token_usage() {
+            let _ = auth.save();
+        }
     }
 }
 
@@ -209,32 +646,13 @@ where
         ctx: &'a foxhole::RequestState,
         _path_iter: &mut foxhole::PathIter,
     ) -> foxhole::resolve::ResolveGuard<Self::Output> {
-        let Some(Ok(token)) = ctx
-            .request
-            .headers()
-            .get("authorization")
-            .map(|i| i.to_str())
-        else {
-            return ResolveGuard::Respond(401u16.response());
-        };
-
-        let Ok(token) = serde_json::from_str(token) else {
-            return ResolveGuard::Respond(401u16.response());
-        };
-
-        let cache = ctx.global_cache.read().unwrap();
-
-        let auth = cache.get::<Authentication>().unwrap().read().unwrap();
-
-        let Some(session) = auth.sessions.get(&token) else {
-            return ResolveGuard::Respond(401u16.response());
-        };
-
-        let Some(user) = auth.users.get(&session.user_id) else {
-            return ResolveGuard::Respond(401u16.response());
-        };
-
-        ResolveGuard::Value(Perm(T::get_permission(&user.permissions)))
+        match resolve_user(ctx) {
+            Ok(user) if user.must_change_password => {
+                ResolveGuard::Respond(PASSWORD_CHANGE_REQUIRED.response())
+            }
+            Ok(user) => ResolveGuard::Value(Perm(T::get_permission(&user.permissions))),
+            Err(status) => ResolveGuard::Respond(reject(status)),
+        }
     }
 }
 
@@ -261,3 +679,278 @@ impl Permission for Control {
         Self(permissions.control.clone())
     }
 }
+
+pub struct Admin(pub bool);
+
+impl Permission for Admin {
+    fn get_permission(permissions: &Permissions) -> Self {
+        Self(permissions.admin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(ids: &[&str]) -> Scope {
+        Scope::Some(ids.iter().map(|i| i.to_string()).collect())
+    }
+
+    fn viewer(user_id: &str, ids: &[&str]) -> User {
+        User::new(
+            user_id.to_string(),
+            &"password".to_string(),
+            Permissions {
+                view: scope(ids),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn parses_bearer_tokens() {
+        let token = Uuid::new_v4();
+
+        assert_eq!(parse_token(&format!("Bearer {}", token)), Some(token));
+        assert_eq!(parse_token(&format!("bearer  {} ", token)), Some(token));
+        assert_eq!(parse_token("Bearer not-a-token"), None);
+    }
+
+    #[test]
+    fn parses_legacy_tokens() {
+        let token = Uuid::new_v4();
+
+        assert_eq!(parse_token(&format!("\"{}\"", token)), Some(token));
+        assert_eq!(parse_token(&token.to_string()), None);
+        assert_eq!(parse_token("Basic abc"), None);
+    }
+
+    #[test]
+    fn template_is_valid() {
+        let authentication = Authentication::default();
+
+        assert!(authentication.validate().is_ok());
+        assert!(authentication.warnings(&ServerConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn rejects_users_under_the_wrong_key() {
+        let mut authentication = Authentication::default();
+
+        authentication
+            .users
+            .insert("alice".to_string(), viewer("bob", &[]));
+
+        assert_eq!(authentication.validate().unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn rejects_users_without_a_password() {
+        let mut authentication = Authentication::default();
+
+        let mut user = viewer("bob", &[]);
+
+        user.password_hash.clear();
+
+        authentication.users.insert("bob".to_string(), user.clone());
+
+        assert!(authentication.validate().is_err());
+
+        user.legacy_password = Some("password".to_string());
+
+        authentication.users.insert("bob".to_string(), user);
+
+        assert!(authentication.validate().is_ok());
+    }
+
+    #[test]
+    fn warns_about_unknown_servers() {
+        let mut authentication = Authentication::default();
+
+        let mut user = viewer("bob", &["example", "missing"]);
+
+        let (token, _) = ApiToken::new(
+            "ci".to_string(),
+            Permissions {
+                control: scope(&["gone"]),
+                ..Default::default()
+            },
+            None,
+        );
+
+        user.tokens.push(token);
+
+        authentication.users.insert("bob".to_string(), user);
+
+        assert!(authentication.validate().is_ok());
+
+        let mut warnings = authentication.warnings(&ServerConfig::default());
+
+        warnings.sort();
+
+        assert_eq!(
+            warnings,
+            [
+                "Token \"ci\" of user \"bob\" has control access to unknown server \"gone\"",
+                "User \"bob\" has view access to unknown server \"missing\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn forgets_removed_servers() {
+        let mut authentication = Authentication::default();
+
+        let mut user = viewer("bob", &["example", "other"]);
+
+        let (token, _) = ApiToken::new(
+            "ci".to_string(),
+            Permissions {
+                view: scope(&["other"]),
+                ..Default::default()
+            },
+            None,
+        );
+
+        user.tokens.push(token);
+
+        authentication.users.insert("bob".to_string(), user);
+
+        assert!(authentication.forget_server("other"));
+        assert!(!authentication.forget_server("other"));
+
+        let user = &authentication.users["bob"];
+
+        assert_eq!(user.permissions.view, scope(&["example"]));
+        assert_eq!(user.tokens[0].permissions.view, scope(&[]));
+    }
+
+    #[test]
+    fn hashes_legacy_passwords() {
+        let bytes = br#"{
+            "users": {
+                "admin": {
+                    "user_id": "admin",
+                    "permissions": { "admin": false, "edit": [], "view": [], "control": [] },
+                    "password": "password"
+                },
+                "bob": {
+                    "user_id": "bob",
+                    "permissions": { "admin": false, "edit": [], "view": ["example"], "control": [] },
+                    "password": "hunter2"
+                }
+            }
+        }"#;
+
+        let mut authentication = Authentication::from_bytes(bytes).unwrap();
+
+        assert!(authentication.migrate());
+        assert!(!authentication.migrate());
+
+        let (admin, bob) = (&authentication.users["admin"], &authentication.users["bob"]);
+
+        assert!(verify_password(&admin.password_hash, "password"));
+        assert!(verify_password(&bob.password_hash, "hunter2"));
+        assert!(!verify_password(&bob.password_hash, "password"));
+
+        assert!(admin.must_change_password);
+        assert!(!bob.must_change_password);
+
+        assert_eq!(admin.permissions, Permissions::admin());
+        assert_eq!(bob.permissions.view, scope(&["example"]));
+        assert!(!bob.permissions.admin);
+
+        let saved = String::from_utf8(authentication.bytes()).unwrap();
+
+        assert!(!saved.contains("hunter2"));
+        assert!(authentication.validate().is_ok());
+    }
+
+    #[test]
+    fn expires_idle_sessions() {
+        let session = Session::new("admin".to_string());
+
+        let now = *session.last_active.lock().unwrap();
+
+        assert!(!session.expired(now + SESSION_IDLE_TIMEOUT - Duration::from_secs(1)));
+        assert!(session.expired(now + SESSION_IDLE_TIMEOUT + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn expires_active_sessions_after_their_length() {
+        let session = Session::new("admin".to_string());
+
+        let end = session.expires;
+
+        *session.last_active.lock().unwrap() = end - Duration::from_secs(1);
+
+        assert!(!session.expired(end - Duration::from_secs(1)));
+        assert!(session.expired(end));
+    }
+
+    #[test]
+    fn rejects_expired_sessions() {
+        let mut authentication = Authentication::default();
+
+        let token = authentication.create_session("admin");
+
+        assert_eq!(authentication.touch(&token).as_deref(), Some("admin"));
+
+        authentication.sessions.get_mut(&token).unwrap().expires = Instant::now();
+
+        assert!(authentication.touch(&token).is_none());
+        assert!(authentication.refresh(&token).is_none());
+    }
+
+    #[test]
+    fn limits_tokens_to_their_owners_permissions() {
+        let mut authentication = Authentication::default();
+
+        let mut user = viewer("bob", &["alpha", "beta"]);
+
+        user.permissions.control = Scope::All;
+
+        let (api_token, token) = ApiToken::new(
+            "ci".to_string(),
+            Permissions {
+                admin: true,
+                edit: Scope::All,
+                view: scope(&["beta", "gamma"]),
+                control: scope(&["alpha"]),
+            },
+            None,
+        );
+
+        user.tokens.push(api_token);
+
+        authentication.users.insert("bob".to_string(), user);
+
+        let permissions = authentication.use_api_token(&token).unwrap().permissions;
+
+        assert_eq!(
+            permissions,
+            Permissions {
+                admin: false,
+                edit: scope(&[]),
+                view: scope(&["beta"]),
+                control: scope(&["alpha"]),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let mut authentication = Authentication::default();
+
+        let (api_token, token) = ApiToken::new("ci".to_string(), Permissions::admin(), Some(0));
+
+        authentication
+            .users
+            .get_mut("admin")
+            .unwrap()
+            .tokens
+            .push(api_token);
+
+        assert!(authentication.use_api_token(&token).is_none());
+    }
+}
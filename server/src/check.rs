@@ -0,0 +1,90 @@
+use std::io::ErrorKind;
+
+use crate::{authentication::Authentication, fs::Config, server_config::ServerConfig};
+
+pub fn load() -> Result<(ServerConfig, Authentication), Vec<String>> {
+    let config = ServerConfig::get().map_err(|e| vec![e.to_string()])?;
+
+    config.validate().map_err(|e| prefixed("servers.json", e))?;
+
+    warn("servers.json", config.warnings());
+
+    let authentication = Authentication::get().map_err(|e| vec![e.to_string()])?;
+
+    authentication
+        .validate()
+        .map_err(|e| prefixed("accounts.json", e))?;
+
+    warn("accounts.json", authentication.warnings(&config));
+
+    Ok((config, authentication))
+}
+
+pub fn check_config() -> i32 {
+    let mut errors = vec![];
+
+    let config = match read::<ServerConfig>(&mut errors) {
+        Some(config) => {
+            if let Err(e) = config.validate() {
+                errors.extend(prefixed("servers.json", e));
+            }
+
+            warn("servers.json", config.warnings());
+
+            config
+        }
+        None => ServerConfig::default(),
+    };
+
+    if let Some(authentication) = read::<Authentication>(&mut errors) {
+        if let Err(e) = authentication.validate() {
+            errors.extend(prefixed("accounts.json", e));
+        }
+
+        warn("accounts.json", authentication.warnings(&config));
+    }
+
+    if errors.is_empty() {
+        println!("Config ok");
+
+        return 0;
+    }
+
+    for error in errors {
+        eprintln!("{}", error);
+    }
+
+    1
+}
+
+fn read<T: Config>(errors: &mut Vec<String>) -> Option<T> {
+    match T::read() {
+        Ok(config) => Some(config),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            println!(
+                "{:?} does not exist, it will be created with defaults on first start",
+                T::full_path()
+            );
+
+            None
+        }
+        Err(e) => {
+            errors.push(e.to_string());
+
+            None
+        }
+    }
+}
+
+pub fn warn(file: &str, warnings: Vec<String>) {
+    for warning in prefixed(file, warnings) {
+        println!("Warning: {}", warning);
+    }
+}
+
+fn prefixed(file: &str, errors: Vec<String>) -> Vec<String> {
+    errors
+        .into_iter()
+        .map(|i| format!("{}: {}", file, i))
+        .collect()
+}
@@ -0,0 +1,128 @@
+use std::net::IpAddr;
+
+use foxhole::{
+    resolve::{Resolve, ResolveGuard},
+    IntoResponse, PathIter, RequestState,
+};
+
+use crate::{proxy, server_config::ServerConfig};
+
+pub struct ClientIp(pub String);
+
+impl<'a> Resolve<'a> for ClientIp {
+    type Output = ClientIp;
+
+    fn resolve(ctx: &'a RequestState, _path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        let Some(peer) = proxy::peer(ctx) else {
+            return ResolveGuard::Respond(403u16.response());
+        };
+
+        let trusted = ctx
+            .global_cache
+            .read()
+            .unwrap()
+            .get::<ServerConfig>()
+            .map(|i| i.read().unwrap().trusted_proxies.clone())
+            .unwrap_or_default();
+
+        let headers = ctx.request.headers();
+
+        let header = |name| headers.get(name).and_then(|i| i.to_str().ok());
+
+        let ip = client_ip(
+            peer,
+            &trusted,
+            header("x-forwarded-for"),
+            header("x-real-ip"),
+        );
+
+        ResolveGuard::Value(ClientIp(ip.to_string()))
+    }
+}
+
+/// Forwarding headers are only believed when they were added by a trusted
+/// proxy, walking `X-Forwarded-For` back to the first hop that is not one.
+fn client_ip(
+    peer: IpAddr,
+    trusted: &[IpAddr],
+    forwarded_for: Option<&str>,
+    real_ip: Option<&str>,
+) -> IpAddr {
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+
+    let Some(forwarded_for) = forwarded_for else {
+        return real_ip.and_then(|i| i.trim().parse().ok()).unwrap_or(peer);
+    };
+
+    let mut ip = peer;
+
+    for hop in forwarded_for.rsplit(',') {
+        let Ok(hop) = hop.trim().parse() else {
+            break;
+        };
+
+        ip = hop;
+
+        if !trusted.contains(&hop) {
+            break;
+        }
+    }
+
+    ip
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_headers_from_untrusted_peers() {
+        let client = client_ip(ip("203.0.113.7"), &[], Some("10.0.0.1"), Some("10.0.0.2"));
+
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn walks_forwarded_for_past_trusted_proxies() {
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        let client = client_ip(
+            ip("10.0.0.1"),
+            &trusted,
+            Some("198.51.100.1, 203.0.113.7, 10.0.0.2"),
+            None,
+        );
+
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn stops_at_unparsable_hops() {
+        let trusted = [ip("10.0.0.1")];
+
+        let client = client_ip(ip("10.0.0.1"), &trusted, Some("garbage, 10.0.0.1"), None);
+
+        assert_eq!(client, ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn falls_back_to_real_ip() {
+        let trusted = [ip("10.0.0.1")];
+
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &trusted, None, Some(" 203.0.113.7 ")),
+            ip("203.0.113.7")
+        );
+
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &trusted, None, Some("unknown")),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use models::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{fs::Config, process::Console};
+
+pub const SHIM: &str = "__detached";
+
+const STDIN: &str = "stdin";
+const STDOUT: &str = "stdout.log";
+const STDERR: &str = "stderr.log";
+const EXIT: &str = "exit";
+
+const TAIL_INTERVAL: Duration = Duration::from_millis(200);
+const KILLED: i32 = 9;
+
+/// Output logs are moved to `stdout.log.1` and `stderr.log.1` once they
+/// reach this size, replacing the previous ones.
+const MAX_LOG_SIZE: u64 = 16 * 1024 * 1024;
+
+/// How long the shim keeps copying output after the server exited, in case
+/// processes it left behind still hold its output open.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Record {
+    pub pid: u32,
+    pub start_time: u64,
+    pub runtime: PathBuf,
+    pub started_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct DetachedState(pub HashMap<String, Record>);
+
+impl Config for DetachedState {
+    fn rel_path(rel: PathBuf) -> PathBuf {
+        rel.join("detached.json")
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+}
+
+pub struct Detached {
+    pid: u32,
+    start_time: u64,
+    runtime: PathBuf,
+    stdin: File,
+    child: Option<Child>,
+    offsets: (u64, u64),
+    running: Arc<AtomicBool>,
+}
+
+impl Detached {
+    pub fn spawn(mut command: Command, runtime: PathBuf, stdin: File) -> io::Result<Self> {
+        let child = command.spawn()?;
+
+        let pid = child.id();
+
+        Ok(Self {
+            pid,
+            start_time: start_time(pid).unwrap_or(0),
+            runtime,
+            stdin,
+            child: Some(child),
+            offsets: (0, 0),
+            running: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    pub fn adopt(record: &Record) -> Option<Self> {
+        if start_time(record.pid) != Some(record.start_time) {
+            return None;
+        }
+
+        let stdin = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(record.runtime.join(STDIN))
+            .ok()?;
+
+        let len = |file| fs::metadata(record.runtime.join(file)).map_or(0, |i| i.len());
+
+        Some(Self {
+            pid: record.pid,
+            start_time: record.start_time,
+            runtime: record.runtime.clone(),
+            stdin,
+            child: None,
+            offsets: (len(STDOUT), len(STDERR)),
+            running: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    pub fn record(&self, started_at: u64) -> Record {
+        Record {
+            pid: self.pid,
+            start_time: self.start_time,
+            runtime: self.runtime.clone(),
+            started_at,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn stdin(&mut self) -> &mut File {
+        &mut self.stdin
+    }
+
+    pub fn attach(&self, console: &Console) {
+        for (file, stream, offset) in [
+            (STDOUT, Stream::Stdout, self.offsets.0),
+            (STDERR, Stream::Stderr, self.offsets.1),
+        ] {
+            let (console, path, running) = (
+                console.clone(),
+                self.runtime.join(file),
+                self.running.clone(),
+            );
+
+            std::thread::spawn(move || tail(&console, &path, offset, stream, &running));
+        }
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        let status = match &mut self.child {
+            Some(child) => match child.try_wait()? {
+                Some(status) => status,
+                None => return Ok(None),
+            },
+            None if start_time(self.pid) == Some(self.start_time) => return Ok(None),
+            None => exit_status(KILLED),
+        };
+
+        self.running.store(false, Ordering::SeqCst);
+
+        Ok(Some(recorded_exit(&self.runtime).unwrap_or(status)))
+    }
+
+    /// How a server that exited while the panel was not running ended.
+    pub fn exit_status(record: &Record) -> ExitStatus {
+        recorded_exit(&record.runtime).unwrap_or(exit_status(KILLED))
+    }
+
+    pub fn wait(&mut self) {
+        while let Ok(None) = self.try_wait() {
+            std::thread::sleep(TAIL_INTERVAL);
+        }
+    }
+
+    pub fn kill(&mut self) -> io::Result<()> {
+        signal_group(self.pid, KILLED)
+    }
+}
+
+fn recorded_exit(runtime: &Path) -> Option<ExitStatus> {
+    fs::read_to_string(runtime.join(EXIT))
+        .ok()
+        .and_then(|i| i.trim().parse().ok())
+        .map(exit_status)
+}
+
+pub fn prepare(program: &str, runtime: &Path) -> io::Result<(Command, File)> {
+    fs::create_dir_all(runtime)?;
+
+    let _ = fs::remove_file(runtime.join(EXIT));
+
+    let fifo = runtime.join(STDIN);
+
+    if !fifo.exists() {
+        mkfifo(&fifo)?;
+    }
+
+    let stdin = OpenOptions::new().read(true).write(true).open(&fifo)?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+
+    command
+        .arg(SHIM)
+        .arg(runtime.join(EXIT))
+        .arg(program)
+        .stdin(stdin.try_clone()?)
+        .stdout(File::create(runtime.join(STDOUT))?)
+        .stderr(File::create(runtime.join(STDERR))?);
+
+    setsid(&mut command);
+
+    Ok((command, stdin))
+}
+
+pub fn shim(args: &[String]) -> ! {
+    let (Some(exit), Some(program)) = (args.first(), args.get(1)) else {
+        std::process::exit(2);
+    };
+
+    let runtime = Path::new(exit).parent().unwrap_or(Path::new("."));
+
+    let mut child = match Command::new(program)
+        .args(&args[2..])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Failed to spawn `{}`: {}", program, e);
+
+            let _ = fs::write(exit, (127 << 8).to_string());
+
+            std::process::exit(127);
+        }
+    };
+
+    ignore_stop_signals();
+
+    let (done, drained) = mpsc::channel();
+
+    let outputs: [(Box<dyn Read + Send>, &str); 2] = [
+        (Box::new(child.stdout.take().unwrap()), STDOUT),
+        (Box::new(child.stderr.take().unwrap()), STDERR),
+    ];
+
+    for (source, file) in outputs {
+        let (path, done) = (runtime.join(file), done.clone());
+
+        std::thread::spawn(move || {
+            copy_rotating(source, &path);
+
+            let _ = done.send(());
+        });
+    }
+
+    let status = match child.wait() {
+        Ok(status) => status,
+        Err(_) => std::process::exit(1),
+    };
+
+    for _ in 0..2 {
+        if drained.recv_timeout(DRAIN_TIMEOUT).is_err() {
+            break;
+        }
+    }
+
+    let _ = fs::write(exit, raw_status(status).to_string());
+
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+fn copy_rotating(mut source: impl Read, path: &Path) {
+    let open = || OpenOptions::new().create(true).append(true).open(path);
+
+    let Ok(mut file) = open() else {
+        return;
+    };
+
+    let mut size = file.metadata().map_or(0, |i| i.len());
+
+    let mut buf = [0; 8192];
+
+    loop {
+        let n = match source.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+
+        if size >= MAX_LOG_SIZE {
+            let _ = fs::rename(path, path.with_extension("log.1"));
+
+            match open() {
+                Ok(new) => file = new,
+                Err(_) => return,
+            }
+
+            size = 0;
+        }
+
+        if file.write_all(&buf[..n]).is_err() {
+            return;
+        }
+
+        size += n as u64;
+    }
+}
+
+fn tail(console: &Console, path: &Path, offset: u64, stream: Stream, running: &AtomicBool) {
+    let Ok(mut file) = File::open(path) else {
+        return;
+    };
+
+    if file.seek(SeekFrom::Start(offset)).is_err() {
+        return;
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+
+    loop {
+        match reader.read_line(&mut line) {
+            // The shim rotated the log, the rest of the output is in a new file.
+            Ok(0) if !same_file(reader.get_ref(), path) => match File::open(path) {
+                Ok(file) => reader = BufReader::new(file),
+                Err(_) => std::thread::sleep(TAIL_INTERVAL),
+            },
+            Ok(0) if running.load(Ordering::SeqCst) => std::thread::sleep(TAIL_INTERVAL),
+            Ok(0) | Err(_) => break,
+            Ok(_) if line.ends_with('\n') => {
+                console.push(stream, line.trim_end_matches(['\r', '\n']).to_string());
+
+                line.clear();
+            }
+            Ok(_) => {}
+        }
+    }
+
+    if !line.is_empty() {
+        console.push(stream, line);
+    }
+}
+
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+
+    match fields.first() {
+        Some(&"Z") | None => None,
+        Some(_) => fields.get(19)?.parse().ok(),
+    }
+}
+
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => true,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_file: &File, _path: &Path) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn setsid(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    unsafe {
+        command.pre_exec(|| match libc::setsid() {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn setsid(_command: &mut Command) {}
+
+#[cfg(unix)]
+fn mkfifo(path: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    match unsafe { libc::mkfifo(path.as_ptr(), 0o600) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+fn mkfifo(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Detached servers are only supported on unix",
+    ))
+}
+
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: i32) -> io::Result<()> {
+    match unsafe { libc::kill(-(pid as libc::pid_t), signal) } {
+        0 => Ok(()),
+        _ => match io::Error::last_os_error() {
+            e if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            e => Err(e),
+        },
+    }
+}
+
+#[cfg(not(unix))]
+fn signal_group(_pid: u32, _signal: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Detached servers are only supported on unix",
+    ))
+}
+
+#[cfg(unix)]
+fn ignore_stop_signals() {
+    unsafe {
+        libc::signal(libc::SIGTERM, libc::SIG_IGN);
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+    }
+}
+
+#[cfg(not(unix))]
+fn ignore_stop_signals() {}
+
+#[cfg(unix)]
+fn exit_status(raw: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    ExitStatus::from_raw(raw)
+}
+
+#[cfg(not(unix))]
+fn exit_status(raw: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(raw as u32)
+}
+
+#[cfg(unix)]
+fn raw_status(status: ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.into_raw()
+}
+
+#[cfg(not(unix))]
+fn raw_status(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
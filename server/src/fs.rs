@@ -1,10 +1,13 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, ErrorKind, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 pub trait Config: Default {
+    /// Whether only the panel's user may read the file.
+    const PRIVATE: bool = false;
+
     fn rel_path(rel: PathBuf) -> PathBuf;
 
     fn full_path() -> PathBuf {
@@ -19,32 +22,116 @@ pub trait Config: Default {
 
     fn bytes(&self) -> Vec<u8>;
 
-    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String>;
 
-    fn get() -> std::io::Result<Self> {
-        match File::open(Self::full_path()) {
-            Ok(mut file) => {
-                let mut buf = vec![];
+    fn migrate(&mut self) -> bool {
+        false
+    }
+
+    fn read() -> std::io::Result<Self> {
+        let path = Self::full_path();
+
+        let mut buf = vec![];
 
-                file.read_to_end(&mut buf)?;
+        File::open(&path)?.read_to_end(&mut buf)?;
+
+        Self::from_bytes(&buf).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to parse {:?}: {}", path, e),
+            )
+        })
+    }
+
+    fn get() -> std::io::Result<Self> {
+        match Self::read() {
+            Ok(mut config) => {
+                if config.migrate() {
+                    config.save()?;
+                }
 
-                Self::from_bytes(&buf).ok_or(io::Error::new(
-                    ErrorKind::Other,
-                    "Failed to parse object from bytes",
-                ))
+                Ok(config)
             }
 
-            Err(_) => {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
                 let config = Self::default();
 
                 config.save()?;
 
                 Ok(config)
             }
+
+            Err(e) => Err(e),
         }
     }
 
+    /// Writes to a temporary file first so the file is never seen half
+    /// written, by the reload watcher or after a crash.
     fn save(&self) -> std::io::Result<()> {
-        File::create(Self::full_path())?.write_all(&self.bytes())
+        let path = Self::full_path();
+
+        let temp = path.with_extension("tmp");
+
+        let _ = fs::remove_file(&temp);
+
+        let mut file = match Self::PRIVATE {
+            true => create_private(&temp)?,
+            false => {
+                let file = File::create(&temp)?;
+
+                if let Ok(metadata) = fs::metadata(&path) {
+                    file.set_permissions(metadata.permissions())?;
+                }
+
+                file
+            }
+        };
+
+        file.write_all(&self.bytes())?;
+        file.sync_all()?;
+
+        fs::rename(temp, path)
+    }
+}
+
+/// Creates a new file only its owner can read or write.
+#[cfg(unix)]
+pub fn create_private(path: &Path) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+pub fn create_private(path: &Path) -> io::Result<File> {
+    File::create(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("kitpanel-test-{}", uuid::Uuid::new_v4()));
+
+        let file = create_private(&path).unwrap();
+
+        let mode = file.metadata().unwrap().permissions().mode();
+
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(create_private(&path).is_ok());
+        assert!(create_private(&path).is_err());
+
+        let _ = fs::remove_file(&path);
     }
 }
@@ -3,18 +3,18 @@ use models::{FromJson, ToJson};
 
 pub struct Json<T>(pub T);
 
-impl<T> IntoResponse for Json<T>
+impl<T> Json<T>
 where
     T: ToJson,
 {
-    fn response(self) -> RawResponse {
+    pub fn with_status(self, status: u16) -> RawResponse {
         let body = self.0.to_json().into_bytes();
 
         let size = body.len();
 
         Response::builder()
             .version(Version::HTTP_11)
-            .status(200)
+            .status(status)
             .header("content-type", "text/json")
             .header("content-length", format!("{}", size))
             .body(body)
@@ -22,6 +22,15 @@ where
     }
 }
 
+impl<T> IntoResponse for Json<T>
+where
+    T: ToJson,
+{
+    fn response(self) -> RawResponse {
+        self.with_status(200)
+    }
+}
+
 impl<'a, T> Resolve<'a> for Json<T>
 where
     T: 'a + FromJson,
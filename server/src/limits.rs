@@ -0,0 +1,316 @@
+use std::{fs, io, path::PathBuf, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+const CPU_PERIOD: u64 = 100_000;
+const CONTROLLERS: [&str; 3] = ["memory", "cpu", "pids"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Limits {
+    pub memory: Option<u64>,
+    pub cpu: Option<f64>,
+    pub open_files: Option<u64>,
+    pub processes: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hit {
+    Memory,
+    Processes,
+    OpenFiles,
+}
+
+impl Hit {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Hit::Memory => "exceeded its memory limit",
+            Hit::Processes => "reached its process limit",
+            Hit::OpenFiles => "reached its open file limit",
+        }
+    }
+}
+
+pub struct Cgroup {
+    path: PathBuf,
+    oom_kills: u64,
+    pids_max: u64,
+}
+
+impl Cgroup {
+    pub fn create(server_id: &str, limits: &Limits) -> Result<Self, String> {
+        let path = delegated()?.join(format!("kitpanel-{}", server_id));
+
+        fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create cgroup {:?}: {}", path, e))?;
+
+        let cgroup = Self::baseline(path);
+
+        if let Err(e) = cgroup.configure(limits) {
+            cgroup.remove();
+
+            return Err(format!(
+                "Failed to configure cgroup {:?}: {}",
+                cgroup.path, e
+            ));
+        }
+
+        Ok(cgroup)
+    }
+
+    /// The cgroup of a server started before the panel restarted, counting
+    /// every limit hit since it was created.
+    pub fn open(server_id: &str) -> Option<Self> {
+        let path = delegated().ok()?.join(format!("kitpanel-{}", server_id));
+
+        path.is_dir().then_some(Self {
+            path,
+            oom_kills: 0,
+            pids_max: 0,
+        })
+    }
+
+    fn baseline(path: PathBuf) -> Self {
+        let cgroup = Self {
+            path,
+            oom_kills: 0,
+            pids_max: 0,
+        };
+
+        Self {
+            oom_kills: cgroup.event("memory.events", "oom_kill"),
+            pids_max: cgroup.event("pids.events", "max"),
+            ..cgroup
+        }
+    }
+
+    fn configure(&self, limits: &Limits) -> io::Result<()> {
+        let write = |file: &str, value: Option<String>| {
+            fs::write(self.path.join(file), value.unwrap_or("max".to_string()))
+        };
+
+        write("memory.max", limits.memory.map(|i| i.to_string()))?;
+        write("pids.max", limits.processes.map(|i| i.to_string()))?;
+        write(
+            "cpu.max",
+            Some(match limits.cpu {
+                Some(cores) => format!("{} {}", (cores * CPU_PERIOD as f64) as u64, CPU_PERIOD),
+                None => format!("max {}", CPU_PERIOD),
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    fn event(&self, file: &str, name: &str) -> u64 {
+        fs::read_to_string(self.path.join(file))
+            .ok()
+            .and_then(|events| {
+                events
+                    .lines()
+                    .filter_map(|i| i.split_once(' '))
+                    .find(|i| i.0 == name)
+                    .and_then(|i| i.1.trim().parse().ok())
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn hits(&self) -> Vec<Hit> {
+        let mut hits = vec![];
+
+        if self.event("memory.events", "oom_kill") > self.oom_kills {
+            hits.push(Hit::Memory);
+        }
+
+        if self.event("pids.events", "max") > self.pids_max {
+            hits.push(Hit::Processes);
+        }
+
+        hits
+    }
+
+    pub fn remove(&self) {
+        let _ = fs::remove_dir(&self.path);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("cgroup {:?}", self.path)
+    }
+}
+
+/// The cgroup servers are created under, with the memory, cpu and pids
+/// controllers enabled for its children.
+///
+/// cgroup v2 only lets a cgroup without processes of its own delegate
+/// controllers. When the panel's cgroup still holds processes, the panel
+/// moves itself into a `panel` child of it; a service manager still counts
+/// the panel under its unit, but any other process left in the parent keeps
+/// the controllers from being enabled and limits fall back to sampling.
+#[cfg(target_os = "linux")]
+fn delegated() -> Result<PathBuf, String> {
+    use std::sync::OnceLock;
+
+    static DELEGATED: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+    DELEGATED
+        .get_or_init(|| {
+            let own = fs::read_to_string("/proc/self/cgroup")
+                .map_err(|e| format!("Failed to read own cgroup: {}", e))?;
+
+            let rel = own
+                .lines()
+                .find_map(|i| i.strip_prefix("0::"))
+                .ok_or("cgroup v2 is not available")?;
+
+            let parent = PathBuf::from("/sys/fs/cgroup").join(rel.trim_start_matches('/'));
+
+            let controllers = fs::read_to_string(parent.join("cgroup.controllers"))
+                .map_err(|e| format!("Failed to read cgroup {:?}: {}", parent, e))?;
+
+            if let Some(missing) = CONTROLLERS
+                .iter()
+                .find(|i| !controllers.split_whitespace().any(|c| c == **i))
+            {
+                return Err(format!(
+                    "The {} controller is not delegated to cgroup {:?}",
+                    missing, parent
+                ));
+            }
+
+            let enable = || {
+                fs::write(
+                    parent.join("cgroup.subtree_control"),
+                    CONTROLLERS.map(|i| format!("+{}", i)).join(" "),
+                )
+            };
+
+            let result = match enable() {
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    let leaf = parent.join("panel");
+
+                    println!(
+                        "[KitPanel] Moving the panel into cgroup {:?} to delegate controllers from {:?}",
+                        leaf, parent
+                    );
+
+                    fs::create_dir_all(&leaf)
+                        .and_then(|_| fs::write(leaf.join("cgroup.procs"), "0"))
+                        .and_then(|_| enable())
+                }
+                result => result,
+            };
+
+            result.map_err(|e| {
+                format!(
+                    "Failed to enable cgroup controllers in {:?}, other processes may share the panel's cgroup: {}",
+                    parent, e
+                )
+            })?;
+
+            Ok(parent)
+        })
+        .clone()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn delegated() -> Result<PathBuf, String> {
+    Err("cgroups are only supported on Linux".to_string())
+}
+
+/// Limits that can only be checked by sampling the running process, as
+/// rlimits and cgroup limits other than an OOM kill don't end it.
+pub fn check(
+    limits: &Limits,
+    cgroup: Option<&Cgroup>,
+    pid: u32,
+    rss_bytes: u64,
+    processes: usize,
+) -> Vec<Hit> {
+    let mut hits = match cgroup {
+        Some(cgroup) => cgroup.hits(),
+        None => {
+            let mut hits = vec![];
+
+            if limits.memory.is_some_and(|i| rss_bytes > i) {
+                hits.push(Hit::Memory);
+            }
+
+            if limits.processes.is_some_and(|i| processes as u64 > i) {
+                hits.push(Hit::Processes);
+            }
+
+            hits
+        }
+    };
+
+    if let Some(max) = limits.open_files {
+        let open = fs::read_dir(format!("/proc/{}/fd", pid)).map_or(0, |i| i.count() as u64);
+
+        if open >= max {
+            hits.push(Hit::OpenFiles);
+        }
+    }
+
+    hits
+}
+
+#[cfg(unix)]
+pub fn apply(command: &mut Command, limits: &Limits, cgroup: Option<&Cgroup>) -> io::Result<()> {
+    use std::os::unix::{io::AsRawFd, process::CommandExt};
+
+    let procs = match cgroup {
+        Some(cgroup) => Some(
+            fs::OpenOptions::new()
+                .write(true)
+                .open(cgroup.path.join("cgroup.procs"))?,
+        ),
+        None => None,
+    };
+
+    let open_files = limits.open_files;
+
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(procs) = &procs {
+                if libc::write(procs.as_raw_fd(), b"0".as_ptr() as *const libc::c_void, 1) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            if let Some(value) = open_files {
+                set_limit(libc::RLIMIT_NOFILE, value)?;
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+fn set_limit(resource: Resource, value: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+
+    match unsafe { libc::setrlimit(resource, &limit) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply(_command: &mut Command, _limits: &Limits, _cgroup: Option<&Cgroup>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Resource limits are only supported on unix",
+    ))
+}
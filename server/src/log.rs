@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use models::Stream;
+use serde::{Deserialize, Serialize};
+
+const LATEST: &str = "latest.log";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Size(u64),
+    Daily,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogConfig {
+    pub rotation: LogRotation,
+    pub retention: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            rotation: LogRotation::Size(10 * 1024 * 1024),
+            retention: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LogFile {
+    dir: PathBuf,
+    config: LogConfig,
+    file: Option<File>,
+    size: u64,
+    opened: NaiveDate,
+}
+
+impl LogFile {
+    pub fn new(dir: PathBuf, config: LogConfig) -> Self {
+        Self {
+            dir,
+            config,
+            file: None,
+            size: 0,
+            opened: Local::now().date_naive(),
+        }
+    }
+
+    pub fn write(&mut self, stream: Stream, content: &str) {
+        if let Err(e) = self.try_write(stream, content) {
+            println!("Failed to write console log in {:?}: {e}", self.dir);
+
+            self.file = None;
+        }
+    }
+
+    fn try_write(&mut self, stream: Stream, content: &str) -> io::Result<()> {
+        let now = Local::now();
+
+        if self.file.is_none() {
+            self.open()?;
+        }
+
+        if self.should_rotate(&now) {
+            self.rotate()?;
+        }
+
+        let line = format!(
+            "[{}] [{}] {}\n",
+            now.format("%Y-%m-%d %H:%M:%S"),
+            stream_name(stream),
+            content
+        );
+
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes())?;
+
+            self.size += line.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(LATEST))?;
+
+        let metadata = file.metadata()?;
+
+        self.size = metadata.len();
+        self.opened = metadata
+            .modified()
+            .map(|i| DateTime::<Local>::from(i).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+        self.file = Some(file);
+
+        Ok(())
+    }
+
+    fn should_rotate(&self, now: &DateTime<Local>) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+
+        match self.config.rotation {
+            LogRotation::Size(max) => self.size >= max,
+            LogRotation::Daily => now.date_naive() != self.opened,
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+
+        let stamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+
+        let rotated = (0..)
+            .map(|i| match i {
+                0 => self.dir.join(format!("{}.log", stamp)),
+                i => self.dir.join(format!("{}_{:03}.log", stamp, i)),
+            })
+            .find(|i| !i.exists())
+            .unwrap();
+
+        fs::rename(self.dir.join(LATEST), rotated)?;
+
+        self.prune()?;
+        self.open()
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let mut rotated: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|i| i.ok())
+            .map(|i| i.path())
+            .filter(|i| {
+                i.extension().is_some_and(|e| e == "log")
+                    && i.file_name().is_some_and(|n| n != LATEST)
+            })
+            .collect();
+
+        rotated.sort();
+
+        let excess = rotated.len().saturating_sub(self.config.retention);
+
+        for path in rotated.into_iter().take(excess) {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn stream_name(stream: Stream) -> &'static str {
+    match stream {
+        Stream::Stdin => "stdin",
+        Stream::Stdout => "stdout",
+        Stream::Stderr => "stderr",
+        Stream::Panel => "panel",
+    }
+}
@@ -1,46 +1,121 @@
+mod audit;
 mod authentication;
+mod check;
+mod client_ip;
+mod detach;
 mod fs;
 mod json;
+mod limits;
+mod log;
+mod metrics;
+mod params;
 mod process;
+mod proxy;
+mod rate_limit;
+mod reload;
+mod secrets;
 mod server_config;
+mod supervisor;
+mod system;
+mod tls;
 
 use std::{
-    collections::HashMap,
-    process::{Command, Stdio},
-    sync::{Arc, RwLock},
-    time::Duration,
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use authentication::{clean_auth, Control, Perm, User, View};
+use audit::{Audit, AuditLog};
+use authentication::{
+    clean_auth, unauthorized, Account, Admin, ApiToken, Control, Edit, Perm, SessionToken, User,
+    View,
+};
+use client_ip::ClientIp;
 use foxhole::{
     action::RawResponse,
     framework::run_with_cache,
     resolve::{Get, Post, Query, UrlPart},
     sys,
-    type_cache::{TypeCache, TypeCacheKey},
+    type_cache::TypeCache,
     IntoResponse, Route,
 };
 use fs::Config;
 use json::Json;
+use metrics::sample_metrics;
 use models::{
-    GlobalStatus, InputCommandRequest, ServerOutput, ServerStatus, TokenRequest, TokenResponse,
+    ClearLockoutRequest, CreateTokenRequest, CreateTokenResponse, CreateUserRequest, ErrorResponse,
+    GlobalStatus, InputCommandRequest, PasswordChangeRequest, Permissions, ServerDefinition,
+    ServerEvent, ServerMetrics, ServerOutput, ServerStatus, ServerUpdate, SystemOverview,
+    TokenRequest, TokenResponse, UpdateUserRequest, UserInfo,
 };
-use process::Process;
-use server_config::ServerConfig;
+use params::Params;
+use process::{Process, ProcessManager, RestartState, StartError};
+use proxy::{Challenge, Front, ProxySecret};
+use rate_limit::{Commands, LoginGuard, Power, RateLimiter, Throttle};
+use secrets::Secrets;
+use server_config::{ServerConfig, ServerInfo};
+use supervisor::supervise;
+use system::Panel;
 
 use crate::authentication::Authentication;
 
 const SESSION_LENGTH: Duration = Duration::from_secs(7200);
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(1800);
+/// How long `api/server/poll` holds a request open waiting for new events.
+/// Each waiting client occupies a server thread for up to this long.
+const POLL_TIMEOUT: Duration = Duration::from_secs(15);
+/// Long-polls allowed to wait at once, so they cannot take every server
+/// thread. Further polls get a 503 and clients fall back to plain polling.
+const MAX_WAITING_POLLS: usize = 16;
+
+static WAITING_POLLS: AtomicUsize = AtomicUsize::new(0);
+
+struct PollSlot;
+
+impl PollSlot {
+    fn take() -> Option<Self> {
+        WAITING_POLLS
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |i| {
+                (i < MAX_WAITING_POLLS).then_some(i + 1)
+            })
+            .ok()
+            .map(|_| PollSlot)
+    }
+}
+
+impl Drop for PollSlot {
+    fn drop(&mut self) {
+        WAITING_POLLS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 fn shared<T>(other: T) -> Arc<RwLock<T>> {
     Arc::new(RwLock::new(other))
 }
 
-#[derive(Default)]
-pub struct ProcessManager(HashMap<String, Process>);
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|i| i.as_secs())
+        .unwrap_or_default()
+}
+
+fn server_status(info: &ServerInfo, process: Option<&Process>) -> ServerStatus {
+    let last_exit = process.and_then(|p| p.last_exit.as_ref());
 
-impl TypeCacheKey for ProcessManager {
-    type Value = Arc<RwLock<ProcessManager>>;
+    ServerStatus {
+        id: info.id.clone(),
+        running: process.map(|p| p.is_alive()).unwrap_or(false),
+        scrollback: info.scrollback,
+        restarts: process.map(|p| p.restart.count).unwrap_or(0),
+        last_exit_code: last_exit.and_then(|i| i.code),
+        last_exit_signal: last_exit.and_then(|i| i.signal),
+        last_exit_reason: last_exit.map(|i| i.reason.clone()),
+        started_at: process.map(|p| unix_time(p.started_at)),
+        stopped_at: process.and_then(|p| p.stopped_at).map(unix_time),
+    }
 }
 
 fn get_all_status(
@@ -56,28 +131,27 @@ fn get_all_status(
         .servers
         .iter()
         .filter(|i| scope.contains(&i.id))
-        .map(|info| {
-            let running = running.get(&info.id).map(|p| p.is_alive()).unwrap_or(false);
-
-            ServerStatus {
-                id: info.id.clone(),
-                running,
-            }
-        })
+        .map(|info| server_status(info, running.get(&info.id)))
         .collect();
 
     Json(GlobalStatus { servers })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn start(
     _p: Post,
     UrlPart(server_id): UrlPart,
     Query(config): Query<ServerConfig>,
+    Query(secrets): Query<Secrets>,
     Query(running): Query<ProcessManager>,
     Perm(Control(scope)): Perm<Control>,
-) -> u16 {
+    _t: Throttle<Power>,
+    audit: Audit,
+) -> RawResponse {
     if !scope.contains(&server_id) {
-        return 401;
+        audit.record("start", Some(&server_id), None, false);
+
+        return 403u16.response();
     }
 
     {
@@ -90,118 +164,482 @@ fn start(
             .unwrap_or(false);
 
         if running {
-            return 200;
+            return 200u16.response();
         }
     }
 
     let config = config.read().unwrap().clone();
 
     let Some(server) = config.servers.iter().find(|i| i.id == server_id) else {
-        return 404;
+        return 404u16.response();
     };
 
-    let dir = config.server_directory.join(server.id.clone());
+    let mut running = running.write().unwrap();
 
-    if std::fs::create_dir_all(&dir).is_err() {
-        return 500;
-    };
+    let secrets = secrets.read().unwrap();
 
-    let mut iter = server.start_command.split_whitespace();
+    if let Err(e) = running.start(&config, server, &secrets) {
+        audit.record("start", Some(&server_id), Some(e.to_string()), false);
 
-    let Some(first) = iter.next() else {
-        return 500;
-    };
+        let status = match e {
+            StartError::InvalidCommand(_) => 400,
+            _ => 500,
+        };
+
+        return Json(ErrorResponse {
+            error: e.to_string(),
+        })
+        .with_status(status);
+    }
+
+    if let Some(process) = running.0.get_mut(&server.id) {
+        process.restart = RestartState::default();
+    }
+
+    audit.record("start", Some(&server_id), None, true);
 
-    let Ok(child) = Command::new(first)
-        .stdout(Stdio::piped())
-        .stdin(Stdio::piped())
-        .args(iter)
-        .current_dir(dir)
-        .spawn()
+    200u16.response()
+}
+
+fn stop(
+    _p: Post,
+    UrlPart(server_id): UrlPart,
+    Query(config): Query<ServerConfig>,
+    Query(running): Query<ProcessManager>,
+    Perm(Control(scope)): Perm<Control>,
+    _t: Throttle<Power>,
+    audit: Audit,
+) -> u16 {
+    if !scope.contains(&server_id) {
+        audit.record("stop", Some(&server_id), None, false);
+
+        return 403;
+    }
+
+    let Some(server) = config
+        .read()
+        .unwrap()
+        .servers
+        .iter()
+        .find(|i| i.id == server_id)
+        .cloned()
     else {
-        return 500;
+        return 404;
     };
 
-    {
-        let mut running = running.write().unwrap();
+    let mut running = running.write().unwrap();
+
+    if let Some(process) = running.0.get_mut(&server_id) {
+        let timeout = Duration::from_secs(server.stop_timeout);
 
-        if let Some(process) = running.0.get_mut(&server.id) {
-            process.insert(child)
-        } else {
-            running.0.insert(server.id.clone(), Process::new(child));
+        if let Err(e) = process.stop(server.stop_command, server.stop_signal, timeout) {
+            audit.record("stop", Some(&server_id), Some(e.to_string()), false);
+
+            return 500;
         }
     }
 
+    audit.record("stop", Some(&server_id), None, true);
+
     200
 }
 
-fn stop(
+fn kill(
     _p: Post,
     UrlPart(server_id): UrlPart,
     Query(running): Query<ProcessManager>,
     Perm(Control(scope)): Perm<Control>,
+    audit: Audit,
 ) -> u16 {
     if !scope.contains(&server_id) {
-        return 401;
+        audit.record("kill", Some(&server_id), None, false);
+
+        return 403;
     }
 
     let mut running = running.write().unwrap();
 
     match running.0.get_mut(&server_id) {
         Some(process) => {
-            if process.kill().is_err() {
+            if let Err(e) = process.kill() {
+                audit.record("kill", Some(&server_id), Some(e.to_string()), false);
+
                 return 500;
             }
         }
         None => {}
     }
 
+    audit.record("kill", Some(&server_id), None, true);
+
     200
 }
 
 fn get_output(
     _g: Get,
     UrlPart(server_id): UrlPart,
+    params: Params,
     Query(running): Query<ProcessManager>,
     Perm(View(scope)): Perm<View>,
 ) -> RawResponse {
     if !scope.contains(&server_id) {
-        return 401u16.response();
+        return 403u16.response();
     }
 
     let running = running.read().unwrap();
 
     let Some(server) = running.0.get(&server_id) else {
-        return Json(ServerOutput { output: None }).response();
+        return Json(ServerOutput {
+            output: None,
+            reset: false,
+        })
+        .response();
     };
 
-    let output = server.console.inner();
+    let (output, reset) = server.console.since(params.get("since"));
 
     Json(ServerOutput {
         output: Some(output),
+        reset,
+    })
+    .response()
+}
+
+fn poll(
+    _g: Get,
+    UrlPart(server_id): UrlPart,
+    params: Params,
+    Query(config): Query<ServerConfig>,
+    Query(running): Query<ProcessManager>,
+    Perm(View(scope)): Perm<View>,
+) -> RawResponse {
+    if !scope.contains(&server_id) {
+        return 403u16.response();
+    }
+
+    let Some(server) = config
+        .read()
+        .unwrap()
+        .servers
+        .iter()
+        .find(|i| i.id == server_id)
+        .cloned()
+    else {
+        return 404u16.response();
+    };
+
+    let Some(_slot) = PollSlot::take() else {
+        return 503u16.response();
+    };
+
+    let since = params.get::<u64>("since");
+    let known = params.get::<bool>("running");
+
+    let deadline = Instant::now() + POLL_TIMEOUT;
+
+    loop {
+        let (console, status) = {
+            let running = running.read().unwrap();
+
+            let process = running.0.get(&server_id);
+
+            (
+                process.map(|p| p.console.clone()),
+                server_status(&server, process),
+            )
+        };
+
+        let generation = console.as_ref().map(|i| i.generation());
+
+        let mut events = vec![];
+
+        if known != Some(status.running) {
+            events.push(ServerEvent::Status(status));
+        }
+
+        if let Some((output, reset)) = console.as_ref().map(|i| i.since(since)) {
+            if !output.is_empty() || (reset && since.is_some()) {
+                events.push(ServerEvent::Output(ServerOutput {
+                    output: Some(output),
+                    reset,
+                }));
+            }
+        }
+
+        let now = Instant::now();
+
+        if !events.is_empty() || now >= deadline {
+            return Json(events).response();
+        }
+
+        let timeout = (deadline - now).min(Duration::from_secs(1));
+
+        match (console, generation) {
+            (Some(console), Some(generation)) => console.wait(generation, timeout),
+            _ => std::thread::sleep(timeout),
+        }
+    }
+}
+
+fn get_metrics(
+    _g: Get,
+    UrlPart(server_id): UrlPart,
+    Query(config): Query<ServerConfig>,
+    Query(running): Query<ProcessManager>,
+    Perm(View(scope)): Perm<View>,
+) -> RawResponse {
+    if !scope.contains(&server_id) {
+        return 403u16.response();
+    }
+
+    if !config
+        .read()
+        .unwrap()
+        .servers
+        .iter()
+        .any(|i| i.id == server_id)
+    {
+        return 404u16.response();
+    }
+
+    let running = running.read().unwrap();
+
+    let process = running.0.get(&server_id).filter(|i| i.is_alive());
+
+    Json(ServerMetrics {
+        id: server_id,
+        uptime: process.and_then(|p| p.started_at.elapsed().ok().map(|i| i.as_secs())),
+        samples: process.map(|p| p.metrics.samples()).unwrap_or_default(),
     })
     .response()
 }
 
+fn get_system(
+    _g: Get,
+    Query(config): Query<ServerConfig>,
+    Query(panel): Query<Panel>,
+    _user: User,
+) -> Json<SystemOverview> {
+    let directory = config.read().unwrap().server_directory.clone();
+
+    Json(panel.read().unwrap().overview(&directory))
+}
+
+fn get_server_config(
+    _g: Get,
+    UrlPart(server_id): UrlPart,
+    Query(config): Query<ServerConfig>,
+    Perm(Edit(scope)): Perm<Edit>,
+) -> RawResponse {
+    if !scope.contains(&server_id) {
+        return 403u16.response();
+    }
+
+    let config = config.read().unwrap();
+
+    match config.servers.iter().find(|i| i.id == server_id) {
+        Some(server) => Json(server.definition()).response(),
+        None => 404u16.response(),
+    }
+}
+
+fn create_server(
+    _p: Post,
+    Json(definition): Json<ServerDefinition>,
+    Query(config): Query<ServerConfig>,
+    Perm(Edit(scope)): Perm<Edit>,
+    audit: Audit,
+) -> RawResponse {
+    if !scope.contains(&definition.id) {
+        audit.record("server_create", Some(&definition.id), None, false);
+
+        return 403u16.response();
+    }
+
+    let detail = definition.start_command.clone();
+
+    let server = match ServerInfo::from_definition(definition) {
+        Ok(server) => server,
+        Err(error) => return Json(ErrorResponse { error }).with_status(400),
+    };
+
+    let mut config = config.write().unwrap();
+
+    if config.servers.iter().any(|i| i.id == server.id) {
+        return Json(ErrorResponse {
+            error: format!("Server {} already exists", server.id),
+        })
+        .with_status(409);
+    }
+
+    let server_id = server.id.clone();
+
+    let mut updated = config.clone();
+
+    updated.servers.push(server);
+
+    if updated.save().is_err() {
+        return 500u16.response();
+    }
+
+    *config = updated;
+
+    audit.record("server_create", Some(&server_id), Some(detail), true);
+
+    200u16.response()
+}
+
+fn update_server(
+    _p: Post,
+    UrlPart(server_id): UrlPart,
+    Json(update): Json<ServerUpdate>,
+    Query(config): Query<ServerConfig>,
+    Perm(Edit(scope)): Perm<Edit>,
+    audit: Audit,
+) -> RawResponse {
+    if !scope.contains(&server_id) {
+        audit.record("server_update", Some(&server_id), None, false);
+
+        return 403u16.response();
+    }
+
+    let fields = [
+        ("display", update.display.is_some()),
+        ("start_command", update.start_command.is_some()),
+        ("env", update.env.is_some()),
+    ];
+
+    let detail = fields
+        .iter()
+        .filter(|i| i.1)
+        .map(|i| i.0)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut config = config.write().unwrap();
+
+    let mut updated = config.clone();
+
+    let Some(server) = updated.servers.iter_mut().find(|i| i.id == server_id) else {
+        return 404u16.response();
+    };
+
+    if let Err(error) = server.apply(update) {
+        return Json(ErrorResponse { error }).with_status(400);
+    }
+
+    if updated.save().is_err() {
+        return 500u16.response();
+    }
+
+    *config = updated;
+
+    audit.record("server_update", Some(&server_id), Some(detail), true);
+
+    200u16.response()
+}
+
+fn remove_server(
+    _p: Post,
+    UrlPart(server_id): UrlPart,
+    Query(config): Query<ServerConfig>,
+    Query(running): Query<ProcessManager>,
+    Query(authentication): Query<Authentication>,
+    Perm(Edit(scope)): Perm<Edit>,
+    audit: Audit,
+) -> RawResponse {
+    if !scope.contains(&server_id) {
+        audit.record("server_remove", Some(&server_id), None, false);
+
+        return 403u16.response();
+    }
+
+    let mut running = running.write().unwrap();
+
+    if running.0.get(&server_id).is_some_and(|p| p.is_alive()) {
+        return Json(ErrorResponse {
+            error: "Stop the server before removing it".to_string(),
+        })
+        .with_status(409);
+    }
+
+    let mut config = config.write().unwrap();
+
+    let Some(index) = config.servers.iter().position(|i| i.id == server_id) else {
+        return 404u16.response();
+    };
+
+    let mut updated = config.clone();
+
+    updated.servers.remove(index);
+
+    if updated.save().is_err() {
+        return 500u16.response();
+    }
+
+    *config = updated;
+
+    running.0.remove(&server_id);
+
+    let mut auth = authentication.write().unwrap();
+
+    if auth.forget_server(&server_id) && auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record("server_remove", Some(&server_id), None, true);
+
+    200u16.response()
+}
+
+#[allow(clippy::too_many_arguments)]
 fn input(
     _p: Post,
     UrlPart(server_id): UrlPart,
     Json(command): Json<InputCommandRequest>,
     Query(processes): Query<ProcessManager>,
+    Query(secrets): Query<Secrets>,
     user: User,
     Perm(Control(scope)): Perm<Control>,
+    _t: Throttle<Commands>,
+    audit: Audit,
 ) -> u16 {
+    let detail = secrets
+        .read()
+        .unwrap()
+        .redact(&server_id, command.command.clone());
+
     if !scope.contains(&server_id) {
-        return 401;
+        audit.record("input", Some(&server_id), Some(detail), false);
+
+        return 403;
     }
 
     let mut processes = processes.write().unwrap();
 
-    let Some(process) = processes.0.get_mut(&server_id) else {
-        return 200;
+    let result = match processes.0.get_mut(&server_id) {
+        Some(process) => process.send(command.command, Some(user.user_id)),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotConnected,
+            "The server is not running",
+        )),
     };
 
-    process.send(command.command, Some(user.user_id));
+    if let Err(e) = result {
+        audit.record(
+            "input",
+            Some(&server_id),
+            Some(format!("{detail} ({e})")),
+            false,
+        );
+
+        return match e.kind() {
+            io::ErrorKind::NotConnected => 409,
+            _ => 500,
+        };
+    }
+
+    audit.record("input", Some(&server_id), Some(detail), true);
 
     200
 }
@@ -209,63 +647,623 @@ fn input(
 fn auth(
     _g: Get,
     Json(request): Json<TokenRequest>,
+    ClientIp(ip): ClientIp,
     Query(authentication): Query<Authentication>,
-) -> Json<TokenResponse> {
+    Query(guard): Query<LoginGuard>,
+    audit: Audit,
+) -> RawResponse {
+    let attempt = guard.write().unwrap().attempt(&request.username, &ip);
+
+    if let Err(remaining) = attempt {
+        audit.record_as(
+            Some(request.username.clone()),
+            "login_failed",
+            None,
+            Some("locked out".to_string()),
+            false,
+        );
+
+        return Json(ErrorResponse {
+            error: format!(
+                "Too many failed logins, try again in {}s",
+                remaining.as_secs() + 1
+            ),
+        })
+        .with_status(429);
+    }
+
     let user = {
         let auth = authentication.read().unwrap();
 
         let Some(user) = auth.get_user(&request.username, &request.password) else {
-            return Json(TokenResponse { token: None });
+            audit.record_as(Some(request.username), "login_failed", None, None, false);
+
+            return Json(TokenResponse {
+                token: None,
+                must_change_password: false,
+            })
+            .response();
         };
 
         user.clone()
     };
 
+    guard
+        .write()
+        .unwrap()
+        .record_success(&request.username, &ip);
+
     let mut auth = authentication.write().unwrap();
 
     let token = auth.create_session(&user.user_id);
 
-    let res = Json(TokenResponse { token: Some(token) });
+    audit.record_as(Some(user.user_id.clone()), "login", None, None, true);
+
+    let res = Json(TokenResponse {
+        token: Some(token),
+        must_change_password: user.must_change_password,
+    });
+
+    res.response()
+}
+
+fn list_lockouts(
+    _g: Get,
+    Query(guard): Query<LoginGuard>,
+    Perm(Admin(admin)): Perm<Admin>,
+) -> RawResponse {
+    if !admin {
+        return 403u16.response();
+    }
 
-    res
+    Json(guard.read().unwrap().lockouts()).response()
+}
+
+fn clear_lockout(
+    _p: Post,
+    Json(request): Json<ClearLockoutRequest>,
+    Query(guard): Query<LoginGuard>,
+    Perm(Admin(admin)): Perm<Admin>,
+    audit: Audit,
+) -> u16 {
+    if !admin {
+        audit.record("lockout_clear", Some(&request.key), None, false);
+
+        return 403;
+    }
+
+    if !guard.write().unwrap().clear(request.kind, &request.key) {
+        return 404;
+    }
+
+    audit.record("lockout_clear", Some(&request.key), None, true);
+
+    200
+}
+
+fn refresh(
+    _p: Post,
+    SessionToken(token): SessionToken,
+    Query(authentication): Query<Authentication>,
+) -> RawResponse {
+    let mut auth = authentication.write().unwrap();
+
+    let Some(user_id) = auth.refresh(&token) else {
+        return unauthorized();
+    };
+
+    let must_change_password = auth
+        .users
+        .get(&user_id)
+        .is_some_and(|i| i.must_change_password);
+
+    Json(TokenResponse {
+        token: Some(token),
+        must_change_password,
+    })
+    .response()
+}
+
+fn logout(
+    _p: Post,
+    SessionToken(token): SessionToken,
+    Query(authentication): Query<Authentication>,
+    audit: Audit,
+) -> u16 {
+    authentication.write().unwrap().revoke(&token);
+
+    audit.record("logout", None, None, true);
+
+    200
+}
+
+fn change_password(
+    _p: Post,
+    Json(request): Json<PasswordChangeRequest>,
+    Query(authentication): Query<Authentication>,
+    Account(user): Account,
+    audit: Audit,
+) -> RawResponse {
+    let mut auth = authentication.write().unwrap();
+
+    if auth
+        .get_user(&user.user_id, &request.current_password)
+        .is_none()
+    {
+        audit.record("password_change", Some(&user.user_id), None, false);
+
+        return 403u16.response();
+    }
+
+    if request.new_password.is_empty() || request.new_password == request.current_password {
+        return Json(ErrorResponse {
+            error: "New password must be non-empty and differ from the current one".to_string(),
+        })
+        .with_status(400);
+    }
+
+    auth.set_password(&user.user_id, &request.new_password);
+
+    if auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record("password_change", Some(&user.user_id), None, true);
+
+    200u16.response()
+}
+
+fn list_users(
+    _g: Get,
+    Query(authentication): Query<Authentication>,
+    Perm(Admin(admin)): Perm<Admin>,
+) -> RawResponse {
+    if !admin {
+        return 403u16.response();
+    }
+
+    let auth = authentication.read().unwrap();
+
+    let mut users: Vec<UserInfo> = auth.users.values().map(|i| i.info()).collect();
+
+    users.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+
+    Json(users).response()
+}
+
+fn create_user(
+    _p: Post,
+    Json(request): Json<CreateUserRequest>,
+    Query(authentication): Query<Authentication>,
+    Query(config): Query<ServerConfig>,
+    Perm(Admin(admin)): Perm<Admin>,
+    audit: Audit,
+) -> RawResponse {
+    if !admin {
+        audit.record("user_create", Some(&request.user_id), None, false);
+
+        return 403u16.response();
+    }
+
+    if request.user_id.trim().is_empty() || request.password.is_empty() {
+        return Json(ErrorResponse {
+            error: "Username and password must not be empty".to_string(),
+        })
+        .with_status(400);
+    }
+
+    if let Some(response) = reject_unknown_servers(&request.permissions, &config) {
+        return response;
+    }
+
+    let mut auth = authentication.write().unwrap();
+
+    if auth.users.contains_key(&request.user_id) {
+        return Json(ErrorResponse {
+            error: format!("User {} already exists", request.user_id),
+        })
+        .with_status(409);
+    }
+
+    let user = User::new(
+        request.user_id.clone(),
+        &request.password,
+        request.permissions,
+    );
+
+    auth.users.insert(request.user_id.clone(), user);
+
+    if auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record("user_create", Some(&request.user_id), None, true);
+
+    200u16.response()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_user(
+    _p: Post,
+    UrlPart(user_id): UrlPart,
+    Json(request): Json<UpdateUserRequest>,
+    Query(authentication): Query<Authentication>,
+    Query(config): Query<ServerConfig>,
+    current: User,
+    SessionToken(token): SessionToken,
+    Perm(Admin(admin)): Perm<Admin>,
+    audit: Audit,
+) -> RawResponse {
+    if !admin {
+        audit.record("user_update", Some(&user_id), None, false);
+
+        return 403u16.response();
+    }
+
+    if user_id == current.user_id && request.permissions.as_ref().is_some_and(|i| !i.admin) {
+        return Json(ErrorResponse {
+            error: "You cannot remove your own admin permission".to_string(),
+        })
+        .with_status(400);
+    }
+
+    if let Some(permissions) = &request.permissions {
+        if let Some(response) = reject_unknown_servers(permissions, &config) {
+            return response;
+        }
+    }
+
+    let mut auth = authentication.write().unwrap();
+
+    let Some(user) = auth.users.get_mut(&user_id) else {
+        return 404u16.response();
+    };
+
+    let mut changed = vec![];
+
+    if let Some(permissions) = request.permissions {
+        user.permissions = permissions;
+        changed.push("permissions");
+    }
+
+    if let Some(password) = request.password.filter(|i| !i.is_empty()) {
+        user.password_hash = authentication::hash_password(&password);
+        user.must_change_password = user_id != current.user_id;
+        changed.push("password");
+
+        auth.end_sessions(&user_id, Some(&token));
+    }
+
+    if auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record(
+        "user_update",
+        Some(&user_id),
+        Some(changed.join(", ")),
+        true,
+    );
+
+    200u16.response()
+}
+
+fn reject_unknown_servers(
+    permissions: &Permissions,
+    config: &RwLock<ServerConfig>,
+) -> Option<RawResponse> {
+    let config = config.read().unwrap();
+
+    let unknown = authentication::unknown_servers(permissions, &config.ids());
+
+    match unknown.is_empty() {
+        true => None,
+        false => Some(
+            Json(ErrorResponse {
+                error: format!("Cannot grant {}", unknown.join(", ")),
+            })
+            .with_status(400),
+        ),
+    }
+}
+
+fn delete_user(
+    _p: Post,
+    UrlPart(user_id): UrlPart,
+    Query(authentication): Query<Authentication>,
+    current: User,
+    Perm(Admin(admin)): Perm<Admin>,
+    audit: Audit,
+) -> RawResponse {
+    if !admin {
+        audit.record("user_delete", Some(&user_id), None, false);
+
+        return 403u16.response();
+    }
+
+    if user_id == current.user_id {
+        return Json(ErrorResponse {
+            error: "You cannot delete your own account".to_string(),
+        })
+        .with_status(400);
+    }
+
+    let mut auth = authentication.write().unwrap();
+
+    if auth.remove_user(&user_id).is_none() {
+        return 404u16.response();
+    }
+
+    if auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record("user_delete", Some(&user_id), None, true);
+
+    200u16.response()
+}
+
+fn list_tokens(
+    _g: Get,
+    Query(authentication): Query<Authentication>,
+    Perm(Admin(admin)): Perm<Admin>,
+) -> RawResponse {
+    if !admin {
+        return 403u16.response();
+    }
+
+    let auth = authentication.read().unwrap();
+
+    let mut tokens = auth.tokens();
+
+    tokens.sort_by_key(|i| i.created);
+
+    Json(tokens).response()
+}
+
+fn create_token(
+    _p: Post,
+    Json(request): Json<CreateTokenRequest>,
+    Query(authentication): Query<Authentication>,
+    Perm(Admin(admin)): Perm<Admin>,
+    audit: Audit,
+) -> RawResponse {
+    if !admin {
+        audit.record("token_create", Some(&request.user_id), None, false);
+
+        return 403u16.response();
+    }
+
+    if request.name.trim().is_empty() {
+        return Json(ErrorResponse {
+            error: "Token name must not be empty".to_string(),
+        })
+        .with_status(400);
+    }
+
+    let expires = request.expires_in.map(|i| unix_time(SystemTime::now()) + i);
+
+    let mut auth = authentication.write().unwrap();
+
+    let Some(user) = auth.users.get_mut(&request.user_id) else {
+        return 404u16.response();
+    };
+
+    let (api_token, token) = ApiToken::new(request.name, request.permissions, expires);
+
+    let id = api_token.id.clone();
+
+    user.tokens.push(api_token);
+
+    if auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record(
+        "token_create",
+        Some(&request.user_id),
+        Some(id.clone()),
+        true,
+    );
+
+    Json(CreateTokenResponse { id, token }).response()
+}
+
+fn revoke_token(
+    _p: Post,
+    UrlPart(token_id): UrlPart,
+    Query(authentication): Query<Authentication>,
+    Perm(Admin(admin)): Perm<Admin>,
+    audit: Audit,
+) -> RawResponse {
+    if !admin {
+        audit.record("token_revoke", None, Some(token_id), false);
+
+        return 403u16.response();
+    }
+
+    let mut auth = authentication.write().unwrap();
+
+    if !auth.revoke_api_token(&token_id) {
+        return 404u16.response();
+    }
+
+    if auth.save().is_err() {
+        return 500u16.response();
+    }
+
+    audit.record("token_revoke", None, Some(token_id), true);
+
+    200u16.response()
+}
+
+fn get_audit(
+    _g: Get,
+    params: Params,
+    Query(log): Query<AuditLog>,
+    Perm(Admin(admin)): Perm<Admin>,
+) -> RawResponse {
+    if !admin {
+        return 403u16.response();
+    }
+
+    Json(log.read().unwrap().query(&params)).response()
+}
+
+fn proxy_check(_g: Get, Challenge(answer): Challenge) -> Json<String> {
+    Json(answer)
 }
 
 fn version(_g: Get) -> Json<String> {
     Json(env!("CARGO_PKG_VERSION").to_string())
 }
 
-fn main() {
-    let router = Route::empty().route("web", sys![]).route(
+fn router() -> Route {
+    Route::empty().route("web", sys![]).route(
         "api",
         Route::empty()
             .route("version", sys![version])
-            .route("auth", sys![auth])
+            .route("proxy", sys![proxy_check])
+            .route(
+                "auth",
+                Route::new(sys![auth])
+                    .route("refresh", sys![refresh])
+                    .route("logout", sys![logout]),
+            )
+            .route(
+                "account",
+                Route::empty().route("password", sys![change_password]),
+            )
             .route("status", sys![get_all_status])
+            .route(
+                "users",
+                Route::empty()
+                    .route("list", sys![list_users])
+                    .route("create", sys![create_user])
+                    .route("update", sys![update_user])
+                    .route("delete", sys![delete_user]),
+            )
+            .route(
+                "lockouts",
+                Route::empty()
+                    .route("list", sys![list_lockouts])
+                    .route("clear", sys![clear_lockout]),
+            )
+            .route(
+                "tokens",
+                Route::empty()
+                    .route("list", sys![list_tokens])
+                    .route("create", sys![create_token])
+                    .route("revoke", sys![revoke_token]),
+            )
+            .route("audit", sys![get_audit])
+            .route("system", sys![get_system])
             .route(
                 "server",
                 Route::empty()
                     .route("start", sys![start])
                     .route("stop", sys![stop])
+                    .route("kill", sys![kill])
                     .route("output", sys![get_output])
+                    .route("poll", sys![poll])
+                    .route("metrics", sys![get_metrics])
+                    .route("config", sys![get_server_config])
+                    .route("create", sys![create_server])
+                    .route("update", sys![update_server])
+                    .route("remove", sys![remove_server])
                     .route("input", sys![input]),
             ),
-    );
+    )
+}
 
-    let mut cache = TypeCache::new();
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).is_some_and(|i| i == detach::SHIM) {
+        detach::shim(&args[2..]);
+    }
+
+    if args.get(1).is_some_and(|i| i == "check-config") {
+        std::process::exit(check::check_config());
+    }
+
+    let panel = shared(Panel::new());
+
+    let (config, auth) = match check::load() {
+        Ok(loaded) => loaded,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+
+            std::process::exit(1);
+        }
+    };
 
-    let auth = shared(Authentication::get().expect("Failed to create users config"));
+    let auth = shared(auth);
 
     let auth_cloned = auth.clone();
 
     std::thread::spawn(|| clean_auth(auth_cloned));
 
-    let config = ServerConfig::get().expect("Failed to construct server config");
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            Some(tls::load(cert, key, &config.address).expect("Failed to load TLS certificate"))
+        }
+        _ => None,
+    };
+
+    let front = Front::bind(format!("{}:{}", config.address, config.port), tls)
+        .expect("Failed to bind listener");
 
-    let address = format!("{}:{}", config.address, config.port);
+    let secrets = shared(Secrets::get().expect("Failed to construct secrets config"));
 
-    cache.insert::<ServerConfig>(shared(config));
-    cache.insert::<ProcessManager>(shared(ProcessManager::default()));
-    cache.insert::<Authentication>(auth);
+    let config = shared(config);
+    let processes = shared(ProcessManager::adopt(
+        &config.read().unwrap(),
+        &secrets.read().unwrap(),
+    ));
 
-    run_with_cache(address, router, cache);
+    let (config_cloned, secrets_cloned, processes_cloned) =
+        (config.clone(), secrets.clone(), processes.clone());
+
+    std::thread::spawn(|| supervise(config_cloned, secrets_cloned, processes_cloned));
+
+    let processes_cloned = processes.clone();
+
+    std::thread::spawn(|| sample_metrics(processes_cloned));
+
+    let (config_cloned, auth_cloned, processes_cloned) =
+        (config.clone(), auth.clone(), processes.clone());
+
+    std::thread::spawn(|| reload::watch(config_cloned, auth_cloned, processes_cloned));
+
+    let (guard, limiter, audit_log, proxy_secret) = (
+        shared(LoginGuard::default()),
+        shared(RateLimiter::default()),
+        shared(AuditLog::new()),
+        front.secret(),
+    );
+
+    let start = move |backend: String| {
+        let mut cache = TypeCache::new();
+
+        cache.insert::<ServerConfig>(config.clone());
+        cache.insert::<Secrets>(secrets.clone());
+        cache.insert::<ProcessManager>(processes.clone());
+        cache.insert::<Authentication>(auth.clone());
+        cache.insert::<Panel>(panel.clone());
+        cache.insert::<LoginGuard>(guard.clone());
+        cache.insert::<RateLimiter>(limiter.clone());
+        cache.insert::<AuditLog>(audit_log.clone());
+        cache.insert::<ProxySecret>(proxy_secret.clone());
+
+        std::thread::spawn(move || run_with_cache(backend, router(), cache))
+    };
+
+    if let Err(e) = front.run(start) {
+        eprintln!("{}", e);
+
+        std::process::exit(1);
+    }
 }
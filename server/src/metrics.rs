@@ -0,0 +1,221 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use models::MetricSample;
+
+use crate::process::ProcessManager;
+
+const INTERVAL: Duration = Duration::from_secs(2);
+const HISTORY: usize = 60;
+
+#[derive(Default)]
+pub struct Metrics {
+    samples: VecDeque<MetricSample>,
+    last: Option<(u32, Instant, u64)>,
+}
+
+impl Metrics {
+    pub fn samples(&self) -> Vec<MetricSample> {
+        self.samples.iter().cloned().collect()
+    }
+
+    fn record(&mut self, pid: u32, usage: Usage) {
+        let now = Instant::now();
+
+        let cpu_percent = match self.last {
+            Some((last_pid, at, ticks)) if last_pid == pid => {
+                let elapsed = (now - at).as_secs_f64();
+                let used = usage.cpu_ticks.saturating_sub(ticks) as f64 / clock_ticks();
+
+                match elapsed > 0.0 {
+                    true => (used / elapsed * 100.0) as f32,
+                    false => 0.0,
+                }
+            }
+            Some(_) => {
+                self.samples.clear();
+                0.0
+            }
+            None => 0.0,
+        };
+
+        self.last = Some((pid, now, usage.cpu_ticks));
+
+        self.samples.push_back(MetricSample {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|i| i.as_secs())
+                .unwrap_or_default(),
+            cpu_percent,
+            rss_bytes: usage.rss_bytes,
+            read_bytes: usage.read_bytes,
+            write_bytes: usage.write_bytes,
+            processes: usage.processes,
+        });
+
+        while self.samples.len() > HISTORY {
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    cpu_ticks: u64,
+    rss_bytes: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+    processes: usize,
+}
+
+pub fn sample_metrics(processes: Arc<RwLock<ProcessManager>>) {
+    loop {
+        std::thread::sleep(INTERVAL);
+
+        let pids: Vec<(String, u32)> = processes
+            .read()
+            .unwrap()
+            .0
+            .iter()
+            .filter_map(|(id, process)| process.pid().map(|pid| (id.clone(), pid)))
+            .collect();
+
+        if pids.is_empty() {
+            continue;
+        }
+
+        let children = process_tree();
+
+        let usage: Vec<(String, u32, Usage)> = pids
+            .into_iter()
+            .filter_map(|(id, pid)| usage(pid, &children).map(|usage| (id, pid, usage)))
+            .collect();
+
+        let mut processes = processes.write().unwrap();
+
+        for (id, pid, usage) in usage {
+            if let Some(process) = processes.0.get_mut(&id) {
+                process.check_limits(usage.rss_bytes, usage.processes);
+                process.metrics.record(pid, usage);
+            }
+        }
+    }
+}
+
+fn usage(pid: u32, children: &HashMap<u32, Vec<u32>>) -> Option<Usage> {
+    let mut usage = Usage::default();
+
+    let mut queue = vec![pid];
+
+    while let Some(pid) = queue.pop() {
+        let Some(stat) = read_stat(pid) else {
+            continue;
+        };
+
+        usage.cpu_ticks += stat.cpu_ticks;
+        usage.rss_bytes += read_rss(pid).unwrap_or(0);
+        usage.processes += 1;
+
+        if let Some((read, write)) = read_io(pid) {
+            usage.read_bytes += read;
+            usage.write_bytes += write;
+        }
+
+        if let Some(children) = children.get(&pid) {
+            queue.extend(children);
+        }
+    }
+
+    match usage.processes {
+        0 => None,
+        _ => Some(usage),
+    }
+}
+
+struct Stat {
+    ppid: u32,
+    cpu_ticks: u64,
+}
+
+fn read_stat(pid: u32) -> Option<Stat> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+
+    Some(Stat {
+        ppid: fields.get(1)?.parse().ok()?,
+        cpu_ticks: utime + stime,
+    })
+}
+
+fn read_rss(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+
+    let kb: u64 = status
+        .lines()
+        .find_map(|i| i.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(kb * 1024)
+}
+
+fn read_io(pid: u32) -> Option<(u64, u64)> {
+    let io = fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+
+    let field = |name: &str| -> Option<u64> {
+        io.lines()
+            .find_map(|i| i.strip_prefix(name))?
+            .trim()
+            .parse()
+            .ok()
+    };
+
+    Some((field("read_bytes:")?, field("write_bytes:")?))
+}
+
+fn process_tree() -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return children;
+    };
+
+    for entry in entries.filter_map(|i| i.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|i| i.parse().ok()) else {
+            continue;
+        };
+
+        if let Some(stat) = read_stat(pid) {
+            children.entry(stat.ppid).or_default().push(pid);
+        }
+    }
+
+    children
+}
+
+#[cfg(unix)]
+fn clock_ticks() -> f64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _ => 100.0,
+    }
+}
+
+#[cfg(not(unix))]
+fn clock_ticks() -> f64 {
+    100.0
+}
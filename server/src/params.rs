@@ -0,0 +1,62 @@
+use std::{collections::HashMap, str::FromStr};
+
+use foxhole::{
+    resolve::{Resolve, ResolveGuard},
+    PathIter, RequestState,
+};
+
+pub struct Params(pub HashMap<String, String>);
+
+impl Params {
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.0.get(key).and_then(|i| i.parse().ok())
+    }
+}
+
+impl<'a> Resolve<'a> for Params {
+    type Output = Params;
+
+    fn resolve(ctx: &'a RequestState, _path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        let query = ctx.request.uri().query().unwrap_or_default();
+
+        let params = query
+            .split('&')
+            .filter(|i| !i.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (decode(key), decode(value)),
+                None => (decode(pair), String::new()),
+            })
+            .collect();
+
+        ResolveGuard::Value(Params(params))
+    }
+}
+
+fn decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -1,146 +1,857 @@
 use std::{
-    collections::VecDeque,
-    io::{self, BufRead, BufReader, Write},
-    process::{Child, ChildStdout},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, RwLock,
-    },
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Stdio},
+    sync::{Arc, Condvar, Mutex, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-const BUFFER_SIZE: usize = 20;
+use foxhole::type_cache::TypeCacheKey;
+use models::{ConsoleLine, Stream};
+
+use crate::{
+    detach::{self, Detached, DetachedState, Record},
+    fs::Config,
+    limits::{self, Cgroup, Hit, Limits},
+    log::LogFile,
+    metrics::Metrics,
+    secrets::{self, Secrets},
+    server_config::{ServerConfig, ServerInfo, StopSignal},
+    unix_time,
+};
 
-pub struct Process {
-    child: Option<Child>,
-    pub console: Console,
-    exit: Arc<AtomicBool>,
+#[derive(Default)]
+pub struct ProcessManager(pub HashMap<String, Process>);
+
+impl TypeCacheKey for ProcessManager {
+    type Value = Arc<RwLock<ProcessManager>>;
 }
 
-impl Process {
-    pub fn new(mut child: Child) -> Self {
-        let exit = Arc::new(AtomicBool::from(false));
+#[derive(Debug)]
+pub enum StartError {
+    InvalidCommand(String),
+    Directory(PathBuf, io::Error),
+    Limits(io::Error),
+    Spawn(String, io::Error),
+}
 
-        let stdout = child.stdout.take().expect("No stdout in Child");
+impl std::fmt::Display for StartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use StartError::*;
 
-        let console = Console::new(stdout, exit.clone());
+        match self {
+            InvalidCommand(e) => write!(f, "{}", e),
+            Directory(path, e) => write!(f, "Failed to create directory {:?}: {}", path, e),
+            Limits(e) => write!(f, "Failed to apply resource limits: {}", e),
+            Spawn(program, e) => write!(f, "Failed to spawn `{}`: {}", program, e),
+        }
+    }
+}
 
-        Self {
-            child: Some(child),
-            console,
-            exit,
+impl std::error::Error for StartError {}
+
+impl ProcessManager {
+    pub fn adopt(config: &ServerConfig, secrets: &Secrets) -> Self {
+        let mut manager = Self::default();
+
+        let Ok(state) = DetachedState::get() else {
+            return manager;
+        };
+
+        for (id, record) in state.0 {
+            let server = config.servers.iter().find(|i| i.id == id);
+
+            let log = LogFile::new(
+                config.server_directory.join(&id).join("logs"),
+                config.logging.clone(),
+            );
+
+            let Some(detached) = Detached::adopt(&record) else {
+                let Some(server) = server else {
+                    continue;
+                };
+
+                let hits = match Cgroup::open(&id) {
+                    Some(cgroup) => {
+                        let hits = cgroup.hits();
+                        cgroup.remove();
+                        hits
+                    }
+                    None => vec![],
+                };
+
+                let info = ExitInfo::new(Detached::exit_status(&record), false, &hits);
+
+                let mut process = Process::stopped(server.scrollback, log);
+
+                process.console.push(
+                    Stream::Panel,
+                    format!(
+                        "[KitPanel] Server {} while the panel was not running",
+                        info.reason
+                    ),
+                );
+
+                process.started_at = UNIX_EPOCH + Duration::from_secs(record.started_at);
+                process.stopped_at = Some(SystemTime::now());
+                process.last_exit = Some(info);
+
+                manager.0.insert(id, process);
+
+                continue;
+            };
+
+            let scrollback = server.map_or(ServerInfo::default_scrollback(), |i| i.scrollback);
+
+            let mut process = Process::stopped(scrollback, log);
+
+            process.console.redact(secrets.redactions(&id));
+            process.insert(Handle::Detached(detached));
+
+            process.started_at = UNIX_EPOCH + Duration::from_secs(record.started_at);
+            process.cgroup = Cgroup::open(&id);
+            process.limits = server.and_then(|i| i.limits.clone());
+
+            let message = match server {
+                Some(_) => format!(
+                    "[KitPanel] Re-attached to detached server (pid {}), earlier output is in {:?}",
+                    record.pid, record.runtime
+                ),
+                None => {
+                    println!(
+                        "Detached server {} (pid {}) is no longer configured, it can still be killed",
+                        id, record.pid
+                    );
+
+                    format!(
+                        "[KitPanel] Re-attached to detached server (pid {}) that is no longer configured, kill it or add it back",
+                        record.pid
+                    )
+                }
+            };
+
+            process.console.push(Stream::Panel, message);
+
+            manager.0.insert(id, process);
         }
+
+        manager.persist();
+
+        manager
     }
 
-    pub fn send(&mut self, input: String, user: Option<String>) {
-        let Some(child) = &mut self.child else {
-            return;
+    pub fn persist(&self) {
+        let state = DetachedState(
+            self.0
+                .iter()
+                .filter_map(|(id, process)| process.record().map(|i| (id.clone(), i)))
+                .collect(),
+        );
+
+        if let Err(e) = state.save() {
+            println!("Failed to save detached server state: {e}");
+        }
+    }
+
+    pub fn start(
+        &mut self,
+        config: &ServerConfig,
+        server: &ServerInfo,
+        secrets: &Secrets,
+    ) -> Result<(), StartError> {
+        let dir = config.server_directory.join(server.id.clone());
+
+        std::fs::create_dir_all(&dir).map_err(|e| StartError::Directory(dir.clone(), e))?;
+
+        let spec = server
+            .start_command
+            .spec()
+            .map_err(StartError::InvalidCommand)?;
+
+        let working_dir = match &spec.working_dir {
+            Some(path) => dir.join(path),
+            None => dir.clone(),
+        };
+
+        let secrets = secrets.for_server(&server.id);
+
+        let runtime = dir.join(".kitpanel");
+
+        let (mut command, stdin) = match server.detached {
+            true => detach::prepare(&spec.program, &runtime)
+                .map(|(command, stdin)| (command, Some(stdin)))
+                .map_err(|e| StartError::Spawn(spec.program.clone(), e))?,
+            false => {
+                let mut command = Command::new(&spec.program);
+
+                command
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .stdin(Stdio::piped());
+
+                (command, None)
+            }
+        };
+
+        if spec.clear_env {
+            command.env_clear();
+        }
+
+        let (cgroup, fallback) = match server
+            .limits
+            .as_ref()
+            .map(|i| Cgroup::create(&server.id, i))
+        {
+            Some(Ok(cgroup)) => (Some(cgroup), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+
+        command
+            .args(&spec.args)
+            .envs(&server.env)
+            .envs(&spec.env)
+            .envs(&secrets)
+            .current_dir(&working_dir);
+
+        let spawned = match &server.limits {
+            Some(limits) => {
+                limits::apply(&mut command, limits, cgroup.as_ref()).map_err(StartError::Limits)
+            }
+            None => Ok(()),
+        }
+        .and_then(|_| {
+            match stdin {
+                Some(stdin) => Detached::spawn(command, runtime, stdin).map(Handle::Detached),
+                None => command.spawn().map(Handle::Attached),
+            }
+            .map_err(|e| StartError::Spawn(spec.program.clone(), e))
+        });
+
+        let handle = match spawned {
+            Ok(handle) => handle,
+            Err(e) => {
+                if let Some(cgroup) = &cgroup {
+                    cgroup.remove();
+                }
+
+                return Err(e);
+            }
         };
 
-        let e = child
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(format!("{}\n", input).as_bytes());
+        let redact = secrets.into_values().filter(|i| !i.is_empty()).collect();
+
+        let process = self.0.entry(server.id.clone()).or_insert_with(|| {
+            Process::stopped(
+                server.scrollback,
+                LogFile::new(dir.join("logs"), config.logging.clone()),
+            )
+        });
+
+        // Before the output readers start, so nothing slips through unredacted.
+        process.console.redact(redact);
+        process.insert(handle);
+
+        if server.limits.is_some() {
+            let message = match (&cgroup, fallback) {
+                (Some(cgroup), _) => format!(
+                    "[KitPanel] Resource limits enforced through {}",
+                    cgroup.describe()
+                ),
+                (None, fallback) => format!(
+                    "[KitPanel] Resource limits enforced through the open file rlimit and sampling of memory and processes, the cpu quota is not applied: {}",
+                    fallback.unwrap_or_default()
+                ),
+            };
+
+            process.console.push(Stream::Panel, message);
+        }
+
+        process.cgroup = cgroup;
+        process.limits = server.limits.clone();
+
+        self.persist();
+
+        Ok(())
+    }
+}
+
+pub enum Handle {
+    Attached(Child),
+    Detached(Detached),
+}
 
-        println!("{e:?}");
+impl Handle {
+    fn id(&self) -> u32 {
+        match self {
+            Handle::Attached(child) => child.id(),
+            Handle::Detached(detached) => detached.id(),
+        }
+    }
+
+    fn attach(&mut self, console: &Console) {
+        match self {
+            Handle::Attached(child) => {
+                let stdout = child.stdout.take().expect("No stdout in Child");
+                let stderr = child.stderr.take().expect("No stderr in Child");
+
+                console.spawn(stdout, stderr);
+            }
+            Handle::Detached(detached) => detached.attach(console),
+        }
+    }
+
+    fn stdin(&mut self) -> Option<&mut dyn Write> {
+        match self {
+            Handle::Attached(child) => child.stdin.as_mut().map(|i| i as &mut dyn Write),
+            Handle::Detached(detached) => Some(detached.stdin()),
+        }
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        match self {
+            Handle::Attached(child) => child.try_wait(),
+            Handle::Detached(detached) => detached.try_wait(),
+        }
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        match self {
+            Handle::Attached(child) => child.kill(),
+            Handle::Detached(detached) => detached.kill(),
+        }
+    }
+
+    fn wait(&mut self) {
+        match self {
+            Handle::Attached(child) => {
+                let _ = child.wait();
+            }
+            Handle::Detached(detached) => detached.wait(),
+        }
+    }
+
+    fn signal(&self, signal: StopSignal) -> io::Result<()> {
+        match self {
+            Handle::Attached(child) => send_signal(child.id() as i32, signal),
+            Handle::Detached(detached) => send_signal(-(detached.id() as i32), signal),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RestartState {
+    pub count: u32,
+    pub recent: VecDeque<Instant>,
+    pub pending: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExitInfo {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub reason: String,
+}
+
+impl ExitInfo {
+    fn new(status: ExitStatus, stopping: bool, hits: &[Hit]) -> Self {
+        let signal = exit_signal(status);
+
+        let reason = match (status.code(), signal) {
+            (Some(0), _) => "exited normally".to_string(),
+            (Some(code), _) => format!("exited with code {}", code),
+            (None, Some(signal)) => format!("killed by signal {}", signal),
+            (None, None) => "exited for an unknown reason".to_string(),
+        };
+
+        let limits = hits.iter().map(|i| i.describe()).collect::<Vec<_>>();
+
+        let reason = match (limits.is_empty(), stopping) {
+            (false, _) => format!("{}, {}", limits.join(", "), reason),
+            (true, true) => format!("stopped from panel, {}", reason),
+            (true, false) => reason,
+        };
+
+        Self {
+            code: status.code(),
+            signal,
+            reason,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+pub struct Process {
+    child: Arc<Mutex<Option<Handle>>>,
+    pub console: Console,
+    pub restart: RestartState,
+    pub last_exit: Option<ExitInfo>,
+    pub started_at: SystemTime,
+    pub stopped_at: Option<SystemTime>,
+    pub cgroup: Option<Cgroup>,
+    pub limits: Option<Limits>,
+    pub metrics: Metrics,
+    hits: Vec<Hit>,
+    stopping: bool,
+}
+
+impl Process {
+    /// A process with nothing running yet, see [`Process::insert`].
+    fn stopped(scrollback: usize, log: LogFile) -> Self {
+        Self {
+            child: Arc::new(Mutex::new(None)),
+            console: Console::new(scrollback, log),
+            restart: RestartState::default(),
+            last_exit: None,
+            started_at: SystemTime::now(),
+            stopped_at: None,
+            cgroup: None,
+            limits: None,
+            metrics: Metrics::default(),
+            hits: vec![],
+            stopping: false,
+        }
+    }
+
+    pub fn send(&mut self, input: String, user: Option<String>) -> io::Result<()> {
+        {
+            let mut child = self.child.lock().unwrap();
+
+            let Some(stdin) = child.as_mut().and_then(|i| i.stdin()) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "The server is not running",
+                ));
+            };
+
+            stdin.write_all(format!("{}\n", input).as_bytes())?;
+            stdin.flush()?;
+        }
 
         let display = match user {
             Some(user) => format!("[KitPanel - {}] {}", user, input),
             None => format!("[KitPanel] {}", input),
         };
 
-        self.console.buf.write().unwrap().insert(display);
+        self.console.push(Stream::Stdin, display);
+
+        Ok(())
     }
 
-    pub fn insert(&mut self, mut child: Child) {
-        self.exit.store(false, Ordering::Relaxed);
+    pub fn insert(&mut self, mut handle: Handle) {
+        self.stopping = false;
+        self.started_at = SystemTime::now();
+        self.stopped_at = None;
+        self.hits.clear();
+
+        let mut child = self.child.lock().unwrap();
 
-        let stdout = child.stdout.take().expect("No stdout in Child");
+        if let Some(mut old) = child.take() {
+            let _ = old.kill();
+            old.wait();
+        }
+
+        handle.attach(&self.console);
 
-        self.child = Some(child);
+        *child = Some(handle);
 
-        self.console.spawn(stdout, self.exit.clone());
+        drop(child);
+
+        self.console.notify();
     }
 
     pub fn is_alive(&self) -> bool {
-        !self.exit.load(Ordering::Relaxed)
+        self.child.lock().unwrap().is_some()
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.child.lock().unwrap().as_ref().map(|i| i.id())
+    }
+
+    pub fn record(&self) -> Option<Record> {
+        match self.child.lock().unwrap().as_ref() {
+            Some(Handle::Detached(detached)) => Some(detached.record(unix_time(self.started_at))),
+            _ => None,
+        }
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping
+    }
+
+    pub fn reap(&mut self) -> Option<ExitStatus> {
+        let status = {
+            let mut child = self.child.lock().unwrap();
+
+            let status = child.as_mut()?.try_wait().ok()??;
+
+            *child = None;
+
+            status
+        };
+
+        if let Some(cgroup) = self.cgroup.take() {
+            for hit in cgroup.hits() {
+                if !self.hits.contains(&hit) {
+                    self.hits.push(hit);
+                }
+            }
+
+            cgroup.remove();
+        }
+
+        let info = ExitInfo::new(status, self.stopping, &self.hits);
+
+        self.console
+            .push(Stream::Panel, format!("[KitPanel] Server {}", info.reason));
+
+        self.last_exit = Some(info);
+        self.stopped_at = Some(SystemTime::now());
+
+        Some(status)
+    }
+
+    pub fn check_limits(&mut self, rss_bytes: u64, processes: usize) {
+        let (Some(limits), Some(pid)) = (&self.limits, self.pid()) else {
+            return;
+        };
+
+        let hits = limits::check(limits, self.cgroup.as_ref(), pid, rss_bytes, processes);
+
+        for hit in hits {
+            if self.hits.contains(&hit) {
+                continue;
+            }
+
+            self.hits.push(hit);
+
+            self.console.push(
+                Stream::Panel,
+                format!("[KitPanel] Server {}", hit.describe()),
+            );
+
+            if matches!(hit, Hit::Memory | Hit::Processes) && self.cgroup.is_none() {
+                if let Some(child) = self.child.lock().unwrap().as_mut() {
+                    let _ = child.kill();
+                }
+            }
+        }
     }
 
     pub fn kill(&mut self) -> io::Result<()> {
-        let Some(mut child) = self.child.take() else {
+        self.stopping = true;
+        self.restart.pending = None;
+
+        match self.child.lock().unwrap().as_mut() {
+            Some(child) => child.kill(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn stop(
+        &mut self,
+        command: Option<String>,
+        signal: Option<StopSignal>,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        self.restart.pending = None;
+
+        if !self.is_alive() {
+            return Ok(());
+        }
+
+        let Some(pid) = self.child.lock().unwrap().as_ref().map(|i| i.id()) else {
             return Ok(());
         };
 
-        child.kill()
+        self.stopping = true;
+
+        match (command, signal) {
+            (Some(command), _) => {
+                if let Err(e) = self.send(command, None) {
+                    self.stopping = false;
+
+                    return Err(e);
+                }
+            }
+            (None, Some(signal)) => {
+                if let Some(child) = self.child.lock().unwrap().as_ref() {
+                    child.signal(signal)?;
+                }
+            }
+            (None, None) => return self.kill(),
+        }
+
+        let child = self.child.clone();
+
+        std::thread::spawn(move || {
+            let deadline = Instant::now() + timeout;
+
+            while Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(100));
+
+                let mut child = child.lock().unwrap();
+
+                let Some(child) = child.as_mut().filter(|i| i.id() == pid) else {
+                    return;
+                };
+
+                if let Ok(Some(_)) = child.try_wait() {
+                    return;
+                }
+            }
+
+            let mut child = child.lock().unwrap();
+
+            if let Some(child) = child.as_mut().filter(|i| i.id() == pid) {
+                let _ = child.kill();
+            }
+        });
+
+        Ok(())
     }
 }
 
+#[cfg(unix)]
+fn send_signal(pid: i32, signal: StopSignal) -> io::Result<()> {
+    let signal = match signal {
+        StopSignal::Term => libc::SIGTERM,
+        StopSignal::Int => libc::SIGINT,
+    };
+
+    match unsafe { libc::kill(pid as libc::pid_t, signal) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+fn send_signal(_pid: i32, _signal: StopSignal) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Stop signals are only supported on unix",
+    ))
+}
+
 #[derive(Debug)]
 pub struct Buffer {
-    buf: VecDeque<String>,
+    buf: VecDeque<ConsoleLine>,
     max: usize,
+    next: u64,
+    log: LogFile,
+    redact: Vec<String>,
 }
 
 impl Buffer {
-    fn new(max: usize) -> Self {
+    fn new(max: usize, log: LogFile) -> Self {
         Self {
             buf: VecDeque::new(),
             max,
+            next: 0,
+            log,
+            redact: Vec::new(),
         }
     }
 
-    fn get(&self) -> &VecDeque<String> {
-        &self.buf
+    fn since(&self, since: Option<u64>) -> (Vec<ConsoleLine>, bool) {
+        let Some(seq) = since.filter(|i| *i < self.next) else {
+            return (self.buf.iter().cloned().collect(), true);
+        };
+
+        let first = self.buf.front().map(|i| i.seq).unwrap_or(self.next);
+
+        let skip = (seq + 1).saturating_sub(first) as usize;
+
+        (self.buf.iter().skip(skip).cloned().collect(), false)
     }
 
-    fn insert(&mut self, content: String) {
-        self.buf.push_front(content);
+    fn insert(&mut self, stream: Stream, content: String) {
+        let content = secrets::redact(&self.redact, content);
 
-        if self.buf.len() > self.max {
-            self.buf.pop_back();
+        self.log.write(stream, &content);
+
+        self.buf.push_back(ConsoleLine {
+            seq: self.next,
+            stream,
+            content,
+        });
+
+        self.next += 1;
+
+        while self.buf.len() > self.max {
+            self.buf.pop_front();
         }
     }
 }
 
+#[derive(Default)]
+struct Changed {
+    generation: Mutex<u64>,
+    condvar: Condvar,
+}
+
+#[derive(Clone)]
 pub struct Console {
     buf: Arc<RwLock<Buffer>>,
+    changed: Arc<Changed>,
 }
 
 impl Console {
-    fn new(source: ChildStdout, exit: Arc<AtomicBool>) -> Self {
-        let buf = Arc::new(RwLock::new(Buffer::new(BUFFER_SIZE)));
+    fn new(scrollback: usize, log: LogFile) -> Self {
+        Self {
+            buf: Arc::new(RwLock::new(Buffer::new(scrollback, log))),
+            changed: Arc::new(Changed::default()),
+        }
+    }
+
+    pub fn spawn(&self, stdout: ChildStdout, stderr: ChildStderr) {
+        let console = self.clone();
+
+        std::thread::spawn(move || handle_output(&console, stdout, Stream::Stdout));
+
+        let console = self.clone();
 
-        let console = Self { buf };
+        std::thread::spawn(move || handle_output(&console, stderr, Stream::Stderr));
+    }
+
+    pub fn redact(&self, secrets: Vec<String>) {
+        self.buf.write().unwrap().redact = secrets;
+    }
+
+    pub fn push(&self, stream: Stream, content: String) {
+        self.buf.write().unwrap().insert(stream, content);
 
-        console.spawn(source, exit);
+        self.notify();
+    }
+
+    pub fn since(&self, since: Option<u64>) -> (Vec<ConsoleLine>, bool) {
+        self.buf.read().unwrap().since(since)
+    }
 
-        console
+    pub fn generation(&self) -> u64 {
+        *self.changed.generation.lock().unwrap()
     }
 
-    pub fn spawn(&self, source: ChildStdout, exit: Arc<AtomicBool>) {
-        let buf = self.buf.clone();
+    pub fn notify(&self) {
+        *self.changed.generation.lock().unwrap() += 1;
 
-        std::thread::spawn(move || handle_stdout(buf, source, exit));
+        self.changed.condvar.notify_all();
     }
 
-    pub fn inner(&self) -> Vec<String> {
-        self.buf.read().unwrap().get().clone().into()
+    pub fn wait(&self, generation: u64, timeout: Duration) {
+        let current = self.changed.generation.lock().unwrap();
+
+        let _ = self
+            .changed
+            .condvar
+            .wait_timeout_while(current, timeout, |i| *i == generation);
     }
 }
 
-fn handle_stdout(buf: Arc<RwLock<Buffer>>, stdout: ChildStdout, sender: Arc<AtomicBool>) {
-    let reader = BufReader::new(stdout);
+fn handle_output(console: &Console, source: impl Read, stream: Stream) {
+    let reader = BufReader::new(source);
 
     for line in reader.lines() {
         match line {
-            Ok(line) => {
-                let mut buf = buf.write().unwrap();
-
-                buf.insert(line);
-            }
+            Ok(line) => console.push(stream, line),
             Err(_) => break,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    sender.store(true, Ordering::Relaxed)
+    fn buffer(max: usize, lines: usize) -> Buffer {
+        let dir = std::env::temp_dir().join(format!("kitpanel-test-{}", uuid::Uuid::new_v4()));
+
+        let mut buffer = Buffer::new(max, LogFile::new(dir.clone(), Default::default()));
+
+        for i in 0..lines {
+            buffer.insert(Stream::Stdout, format!("line {}", i));
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+
+        buffer
+    }
+
+    fn redacted(secrets: &[&str], line: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("kitpanel-test-{}", uuid::Uuid::new_v4()));
+
+        let mut buffer = Buffer::new(10, LogFile::new(dir.clone(), Default::default()));
+
+        buffer.redact = secrets.iter().map(|i| i.to_string()).collect();
+
+        buffer.insert(Stream::Stdout, line.to_string());
+
+        let _ = std::fs::remove_dir_all(dir);
+
+        buffer.buf[0].content.clone()
+    }
+
+    fn seqs(lines: Vec<ConsoleLine>) -> Vec<u64> {
+        lines.into_iter().map(|i| i.seq).collect()
+    }
+
+    #[test]
+    fn returns_everything_without_a_cursor() {
+        let (lines, reset) = buffer(10, 3).since(None);
+
+        assert_eq!(seqs(lines), [0, 1, 2]);
+        assert!(reset);
+    }
+
+    #[test]
+    fn returns_lines_after_the_cursor() {
+        let buffer = buffer(10, 5);
+
+        let (lines, reset) = buffer.since(Some(2));
+
+        assert_eq!(seqs(lines), [3, 4]);
+        assert!(!reset);
+
+        let (lines, reset) = buffer.since(Some(4));
+
+        assert!(lines.is_empty());
+        assert!(!reset);
+    }
+
+    #[test]
+    fn skips_lines_that_scrolled_away() {
+        let (lines, reset) = buffer(3, 10).since(Some(2));
+
+        assert_eq!(seqs(lines), [7, 8, 9]);
+        assert!(!reset);
+    }
+
+    #[test]
+    fn resets_on_a_cursor_from_the_future() {
+        let (lines, reset) = buffer(10, 2).since(Some(5));
+
+        assert_eq!(seqs(lines), [0, 1]);
+        assert!(reset);
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        assert_eq!(
+            redacted(&["hunter2", "s3cret"], "login hunter2 s3cret hunter2"),
+            "login ******** ******** ********"
+        );
+        assert_eq!(redacted(&[], "nothing to hide"), "nothing to hide");
+    }
 }
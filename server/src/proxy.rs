@@ -0,0 +1,927 @@
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{IpAddr, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use foxhole::{
+    resolve::{Resolve, ResolveGuard},
+    type_cache::TypeCacheKey,
+    PathIter, RequestState,
+};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::tls::invalid;
+
+const PEER_HEADER: &str = "x-kitpanel-peer";
+const SECRET_HEADER: &str = "x-kitpanel-secret";
+const CHALLENGE_HEADER: &str = "x-kitpanel-challenge";
+
+const MAX_HEAD: usize = 64 * 1024;
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+const WORKERS: usize = 64;
+const BACKEND_ATTEMPTS: usize = 5;
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+const BACKEND_TIMEOUT: Duration = Duration::from_secs(120);
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shared by the front listener and the HTTP backend, so the backend only
+/// serves requests relayed by the panel itself.
+pub struct ProxySecret(String);
+
+impl TypeCacheKey for ProxySecret {
+    type Value = Arc<ProxySecret>;
+}
+
+impl ProxySecret {
+    fn answer(&self, challenge: &str) -> String {
+        format!("{:x}", Sha256::digest(format!("{}:{}", self.0, challenge)))
+    }
+
+    fn matches(&self, other: &str) -> bool {
+        self.0.len() == other.len()
+            && self
+                .0
+                .bytes()
+                .zip(other.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+/// The address of the client a request was relayed for, or `None` if the
+/// request did not come through the front listener.
+pub fn peer(ctx: &RequestState) -> Option<IpAddr> {
+    let headers = ctx.request.headers();
+
+    let secret = headers.get(SECRET_HEADER)?.to_str().ok()?;
+
+    let cache = ctx.global_cache.read().unwrap();
+
+    if !cache.get::<ProxySecret>()?.matches(secret) {
+        return None;
+    }
+
+    headers.get(PEER_HEADER)?.to_str().ok()?.parse().ok()
+}
+
+/// Answers the startup challenge of the front listener.
+pub struct Challenge(pub String);
+
+impl<'a> Resolve<'a> for Challenge {
+    type Output = Challenge;
+
+    fn resolve(ctx: &'a RequestState, _path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        let Some(challenge) = ctx
+            .request
+            .headers()
+            .get(CHALLENGE_HEADER)
+            .and_then(|i| i.to_str().ok())
+        else {
+            return ResolveGuard::None;
+        };
+
+        let cache = ctx.global_cache.read().unwrap();
+
+        match cache.get::<ProxySecret>() {
+            Some(secret) => ResolveGuard::Value(Challenge(secret.answer(challenge))),
+            None => ResolveGuard::None,
+        }
+    }
+}
+
+/// The listener clients connect to. Each request is relayed to a loopback
+/// HTTP backend together with the client address, which the backend can't
+/// see itself, and TLS is terminated here if configured.
+pub struct Front {
+    listener: TcpListener,
+    tls: Option<Arc<ServerConfig>>,
+    secret: Arc<ProxySecret>,
+}
+
+impl Front {
+    pub fn bind(address: String, tls: Option<Arc<ServerConfig>>) -> io::Result<Self> {
+        let secret = Arc::new(ProxySecret(format!(
+            "{}{}",
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        )));
+
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            tls,
+            secret,
+        })
+    }
+
+    pub fn secret(&self) -> Arc<ProxySecret> {
+        self.secret.clone()
+    }
+
+    /// Starts the backend with `start` on a free loopback port and relays
+    /// clients to it once it has proven it knows the secret.
+    ///
+    /// The port is only probed, so another process can take it before the
+    /// backend binds it. The backend then stops or the other process fails
+    /// the challenge, and another port is tried; no client is ever relayed
+    /// to a backend that wasn't verified.
+    pub fn run(self, start: impl Fn(String) -> JoinHandle<()>) -> io::Result<()> {
+        for _ in 0..BACKEND_ATTEMPTS {
+            let backend = TcpListener::bind("127.0.0.1:0")?.local_addr()?.to_string();
+
+            let handle = start(backend.clone());
+
+            if verify(&backend, &self.secret, &handle) {
+                self.accept(backend);
+
+                return Ok(());
+            }
+
+            println!(
+                "The HTTP backend on {} could not be verified, trying another port",
+                backend
+            );
+        }
+
+        Err(io::Error::new(
+            ErrorKind::AddrInUse,
+            "Failed to start a verified HTTP backend",
+        ))
+    }
+
+    /// Hands connections to a fixed set of workers, each relaying one client
+    /// at a time. Connections wait in the queue and then the listen backlog
+    /// while every worker is busy.
+    fn accept(self, backend: String) {
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(WORKERS);
+
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let relay = Arc::new(Relay {
+            backend,
+            secret: self.secret,
+            tls: self.tls,
+        });
+
+        for _ in 0..WORKERS {
+            let (receiver, relay) = (receiver.clone(), relay.clone());
+
+            std::thread::spawn(move || loop {
+                let Ok(stream) = receiver.lock().unwrap().recv() else {
+                    return;
+                };
+
+                if let Err(e) = relay.handle(stream) {
+                    if e.kind() != ErrorKind::WouldBlock && e.kind() != ErrorKind::TimedOut {
+                        println!("Connection closed: {e}");
+                    }
+                }
+            });
+        }
+
+        for stream in self.listener.incoming().flatten() {
+            if sender.send(stream).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn verify(backend: &str, secret: &ProxySecret, handle: &JoinHandle<()>) -> bool {
+    let challenge = Uuid::new_v4().simple().to_string();
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+
+    while Instant::now() < deadline && !handle.is_finished() {
+        match ask(backend, &challenge) {
+            Ok(answer) => return answer == secret.answer(&challenge),
+            Err(_) => std::thread::sleep(Duration::from_millis(100)),
+        }
+    }
+
+    false
+}
+
+fn ask(backend: &str, challenge: &str) -> io::Result<String> {
+    let mut stream = TcpStream::connect(backend)?;
+
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    write!(
+        stream,
+        "GET /api/proxy HTTP/1.1\r\nhost: {}\r\n{}: {}\r\nconnection: close\r\n\r\n",
+        backend, CHALLENGE_HEADER, challenge
+    )?;
+
+    let mut stream = BufReader::new(stream);
+
+    let head = Head::read(&mut stream)?.ok_or_else(|| invalid("Empty response"))?;
+
+    let mut body = vec![];
+
+    stream
+        .take(head.content_length.unwrap_or(0))
+        .read_to_end(&mut body)?;
+
+    serde_json::from_slice(&body).map_err(invalid)
+}
+
+struct Relay {
+    backend: String,
+    secret: Arc<ProxySecret>,
+    tls: Option<Arc<ServerConfig>>,
+}
+
+impl Relay {
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let peer = stream.peer_addr()?.ip();
+
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+        match &self.tls {
+            Some(config) => {
+                let connection = ServerConnection::new(config.clone()).map_err(invalid)?;
+
+                self.relay(StreamOwned::new(connection, stream), peer)
+            }
+            None => self.relay(stream, peer),
+        }
+    }
+
+    /// Relays requests from one client until either side closes, keeping
+    /// the backend connection open in between.
+    ///
+    /// Request bodies are read completely before they are forwarded with a
+    /// `content-length` of their own, so the backend never has to agree with
+    /// the proxy on how a body is framed.
+    fn relay(&self, client: impl Read + Write, peer: IpAddr) -> io::Result<()> {
+        let mut client = BufReader::new(client);
+
+        let mut upstream = None;
+
+        loop {
+            let request = match Head::read(&mut client) {
+                Ok(Some(request)) => request,
+                Ok(None) => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    return reject(client.get_mut(), "400 Bad Request");
+                }
+                Err(e) => return Err(e),
+            };
+
+            let framing = match request.framing() {
+                Framing::Other => return reject(client.get_mut(), "501 Not Implemented"),
+                Framing::Length(length) if length > MAX_BODY => {
+                    return reject(client.get_mut(), "413 Content Too Large");
+                }
+                framing => framing,
+            };
+
+            match request.get("expect") {
+                Some(i) if !i.eq_ignore_ascii_case("100-continue") => {
+                    return reject(client.get_mut(), "417 Expectation Failed");
+                }
+                // The expectation isn't forwarded, so the proxy answers it
+                Some(_) if !matches!(framing, Framing::Absent | Framing::Length(0)) => {
+                    client
+                        .get_mut()
+                        .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                    client.get_mut().flush()?;
+                }
+                _ => {}
+            }
+
+            let body = match framing {
+                Framing::Length(length) => {
+                    let mut body = vec![0; length as usize];
+
+                    client.read_exact(&mut body)?;
+
+                    body
+                }
+                Framing::Chunked => match read_chunked(&mut client, MAX_BODY) {
+                    Ok(Some(body)) => body,
+                    Ok(None) => return reject(client.get_mut(), "413 Content Too Large"),
+                    Err(e) if e.kind() == ErrorKind::InvalidData => {
+                        return reject(client.get_mut(), "400 Bad Request");
+                    }
+                    Err(e) => return Err(e),
+                },
+                _ => vec![],
+            };
+
+            let mut head = request.forward();
+
+            head.push_str(&format!("content-length: {}\r\n", body.len()));
+            head.push_str(&format!("{}: {}\r\n", PEER_HEADER, peer));
+            head.push_str(&format!("{}: {}\r\n\r\n", SECRET_HEADER, self.secret.0));
+
+            let (response, backend) = self.send(&mut upstream, head.as_bytes(), &body)?;
+
+            let framing = match response.status() {
+                _ if request.method() == "HEAD" => Framing::Length(0),
+                Some(100..=199 | 204 | 304) => Framing::Length(0),
+                _ => response.framing(),
+            };
+
+            // Without a length the body only ends when the backend closes
+            let until_close = matches!(framing, Framing::Absent | Framing::Other);
+
+            let close = request.close || until_close;
+
+            let mut head = response.forward();
+
+            if let Some(length) = response.content_length {
+                head.push_str(&format!("content-length: {}\r\n", length));
+            }
+
+            if let Some(encoding) = &response.transfer_encoding {
+                head.push_str(&format!("transfer-encoding: {}\r\n", encoding));
+            }
+
+            if close {
+                head.push_str("connection: close\r\n");
+            }
+
+            head.push_str("\r\n");
+
+            let client = client.get_mut();
+
+            client.write_all(head.as_bytes())?;
+
+            match framing {
+                Framing::Length(length) => {
+                    if io::copy(&mut backend.by_ref().take(length), client)? < length {
+                        return Err(ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                Framing::Chunked => copy_chunked(backend, client)?,
+                _ => {
+                    io::copy(backend, client)?;
+                }
+            }
+
+            client.flush()?;
+
+            if until_close || response.close {
+                upstream = None;
+            }
+
+            if close {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends a request over the kept backend connection, connecting again
+    /// if the backend closed it before answering.
+    fn send<'a>(
+        &self,
+        upstream: &'a mut Option<BufReader<TcpStream>>,
+        head: &[u8],
+        body: &[u8],
+    ) -> io::Result<(Head, &'a mut BufReader<TcpStream>)> {
+        if let Some(mut connection) = upstream.take() {
+            if let Some(response) = exchange(&mut connection, head, body)? {
+                return Ok((response, upstream.insert(connection)));
+            }
+        }
+
+        let stream = TcpStream::connect(&self.backend)?;
+
+        stream.set_read_timeout(Some(BACKEND_TIMEOUT))?;
+
+        let connection = upstream.insert(BufReader::new(stream));
+
+        let response = exchange(connection, head, body)?
+            .ok_or_else(|| invalid("The backend closed the connection"))?;
+
+        Ok((response, connection))
+    }
+}
+
+/// Writes a request and reads the response head, `None` if the connection
+/// was closed before any of the response arrived.
+fn exchange(
+    connection: &mut BufReader<TcpStream>,
+    head: &[u8],
+    body: &[u8],
+) -> io::Result<Option<Head>> {
+    let stream = connection.get_mut();
+
+    if stream
+        .write_all(head)
+        .and_then(|_| stream.write_all(body))
+        .is_err()
+    {
+        return Ok(None);
+    }
+
+    match Head::read(connection) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe
+            ) =>
+        {
+            Ok(None)
+        }
+        result => result,
+    }
+}
+
+fn reject(client: &mut impl Write, status: &str) -> io::Result<()> {
+    write!(
+        client,
+        "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        status
+    )?;
+
+    client.flush()
+}
+
+/// Reads a line of at most `MAX_HEAD` bytes without its line ending.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+
+    match reader.take(MAX_HEAD as u64 + 1).read_line(&mut line)? {
+        0 => Err(ErrorKind::UnexpectedEof.into()),
+        n if n > MAX_HEAD => Err(invalid("Line too long")),
+        _ => Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+/// Reads the size line of a chunk, ignoring chunk extensions.
+fn chunk_size(reader: &mut impl BufRead) -> io::Result<u64> {
+    let line = read_line(reader)?;
+
+    let size = line.split(';').next().unwrap_or_default().trim();
+
+    if size.is_empty() || !size.bytes().all(|i| i.is_ascii_hexdigit()) {
+        return Err(invalid("Invalid chunk size"));
+    }
+
+    u64::from_str_radix(size, 16).map_err(invalid)
+}
+
+/// Reads the line ending a chunk's data, or the trailers after the last one.
+fn end_chunk(reader: &mut impl BufRead, last: bool) -> io::Result<()> {
+    loop {
+        match read_line(reader)?.is_empty() {
+            true => return Ok(()),
+            false if last => continue,
+            false => return Err(invalid("Chunk data is longer than its size")),
+        }
+    }
+}
+
+/// Reads a chunked body, `None` if it is larger than `max`.
+fn read_chunked(reader: &mut impl BufRead, max: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut body = vec![];
+
+    loop {
+        let size = chunk_size(reader)?;
+
+        if size == 0 {
+            end_chunk(reader, true)?;
+
+            return Ok(Some(body));
+        }
+
+        if body.len() as u64 + size > max {
+            return Ok(None);
+        }
+
+        let start = body.len();
+
+        body.resize(start + size as usize, 0);
+
+        reader.read_exact(&mut body[start..])?;
+
+        end_chunk(reader, false)?;
+    }
+}
+
+/// Copies a chunked body chunk by chunk, dropping extensions and trailers.
+fn copy_chunked(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+    loop {
+        let size = chunk_size(reader)?;
+
+        write!(writer, "{:x}\r\n", size)?;
+
+        if size == 0 {
+            end_chunk(reader, true)?;
+
+            return writer.write_all(b"\r\n");
+        }
+
+        if io::copy(&mut reader.by_ref().take(size), writer)? < size {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        end_chunk(reader, false)?;
+
+        writer.write_all(b"\r\n")?;
+    }
+}
+
+/// How the end of a body is found.
+#[derive(Debug, PartialEq, Eq)]
+enum Framing {
+    /// Neither `content-length` nor `transfer-encoding` was given.
+    Absent,
+    Length(u64),
+    Chunked,
+    /// A `transfer-encoding` other than plain `chunked`.
+    Other,
+}
+
+struct Head {
+    start: String,
+    headers: Vec<(String, String)>,
+    content_length: Option<u64>,
+    transfer_encoding: Option<String>,
+    close: bool,
+}
+
+impl Head {
+    /// Reads a request or response head, `None` on a clean end of stream.
+    ///
+    /// Heads the proxy and the backend could frame differently are rejected:
+    /// repeated or malformed `content-length`, `content-length` together
+    /// with `transfer-encoding`, folded lines and stray carriage returns.
+    fn read(reader: &mut impl BufRead) -> io::Result<Option<Self>> {
+        let mut lines = vec![];
+        let mut size = 0;
+
+        loop {
+            let mut line = String::new();
+
+            let n = reader
+                .take((MAX_HEAD - size) as u64 + 1)
+                .read_line(&mut line)?;
+
+            size += n;
+
+            match n {
+                0 if lines.is_empty() => return Ok(None),
+                0 => return Err(invalid("Connection closed mid head")),
+                _ if size > MAX_HEAD => return Err(invalid("Head too large")),
+                _ => {}
+            }
+
+            let line = line
+                .strip_suffix('\n')
+                .map(|i| i.strip_suffix('\r').unwrap_or(i))
+                .unwrap_or(&line)
+                .to_string();
+
+            match line.is_empty() {
+                true if lines.is_empty() => continue,
+                true => break,
+                false => lines.push(line),
+            }
+        }
+
+        if lines.iter().any(|i| i.contains('\r')) {
+            return Err(invalid("Stray carriage return"));
+        }
+
+        let start = lines.remove(0);
+
+        let mut headers = vec![];
+
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(invalid("Header without a value"));
+            };
+
+            if name.is_empty() || name.contains(|i: char| i.is_whitespace()) {
+                return Err(invalid("Malformed header"));
+            }
+
+            headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        let all = |name: &str| {
+            headers
+                .iter()
+                .filter(|i| i.0 == name)
+                .map(|i| i.1.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        let content_length = match all("content-length")[..] {
+            [] => None,
+            [length] if !length.is_empty() && length.bytes().all(|i| i.is_ascii_digit()) => {
+                Some(length.parse().map_err(invalid)?)
+            }
+            [_] => return Err(invalid("Invalid content-length")),
+            _ => return Err(invalid("Repeated content-length")),
+        };
+
+        let transfer_encoding = match all("transfer-encoding") {
+            encodings if encodings.is_empty() => None,
+            encodings => Some(encodings.join(", ")),
+        };
+
+        if content_length.is_some() && transfer_encoding.is_some() {
+            return Err(invalid("Both content-length and transfer-encoding"));
+        }
+
+        let close = start.ends_with("HTTP/1.0")
+            || all("connection")
+                .iter()
+                .any(|i| i.eq_ignore_ascii_case("close"));
+
+        Ok(Some(Self {
+            start,
+            headers,
+            content_length,
+            transfer_encoding,
+            close,
+        }))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|i| i.0 == name)
+            .map(|i| i.1.as_str())
+    }
+
+    fn framing(&self) -> Framing {
+        match (&self.transfer_encoding, self.content_length) {
+            (Some(i), _) if i.eq_ignore_ascii_case("chunked") => Framing::Chunked,
+            (Some(_), _) => Framing::Other,
+            (None, Some(length)) => Framing::Length(length),
+            (None, None) => Framing::Absent,
+        }
+    }
+
+    fn method(&self) -> &str {
+        self.start.split(' ').next().unwrap_or_default()
+    }
+
+    fn status(&self) -> Option<u16> {
+        self.start.split(' ').nth(1)?.parse().ok()
+    }
+
+    /// The head without hop-by-hop headers, framing headers or headers only
+    /// the proxy may set, and without the terminating blank line.
+    fn forward(&self) -> String {
+        let mut head = format!("{}\r\n", self.start);
+
+        for (name, value) in self.headers.iter() {
+            if matches!(
+                name.as_str(),
+                "connection"
+                    | "keep-alive"
+                    | "proxy-connection"
+                    | "te"
+                    | "trailer"
+                    | "upgrade"
+                    | "expect"
+                    | "content-length"
+                    | "transfer-encoding"
+            ) || name.starts_with("x-kitpanel-")
+            {
+                continue;
+            }
+
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        head
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    fn head(raw: &str) -> io::Result<Option<Head>> {
+        Head::read(&mut Cursor::new(raw.as_bytes()))
+    }
+
+    /// Answers every request with its start line, the relayed peer and its
+    /// body, optionally closing the connection after each response.
+    fn backend(close: bool) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let address = listener.local_addr().unwrap().to_string();
+
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                std::thread::spawn(move || echo(stream, close));
+            }
+        });
+
+        (address, connections)
+    }
+
+    fn echo(stream: TcpStream, close: bool) -> io::Result<()> {
+        let mut stream = BufReader::new(stream);
+
+        while let Some(request) = Head::read(&mut stream)? {
+            let mut body = vec![0; request.content_length.unwrap_or(0) as usize];
+
+            stream.read_exact(&mut body)?;
+
+            let reply = format!(
+                "{} {} {}",
+                request.start,
+                request.get(PEER_HEADER).unwrap_or("-"),
+                String::from_utf8_lossy(&body)
+            );
+
+            write!(
+                stream.get_mut(),
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                reply.len(),
+                reply
+            )?;
+
+            if close {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    struct Client {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Client {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Client {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn relay(backend: &str, requests: &str) -> String {
+        let relay = Relay {
+            backend: backend.to_string(),
+            secret: Arc::new(ProxySecret("secret".to_string())),
+            tls: None,
+        };
+
+        let mut client = Client {
+            input: Cursor::new(requests.as_bytes().to_vec()),
+            output: vec![],
+        };
+
+        relay
+            .relay(&mut client, "10.0.0.1".parse().unwrap())
+            .unwrap();
+
+        String::from_utf8(client.output).unwrap()
+    }
+
+    #[test]
+    fn reads_heads() {
+        let request = head(
+            "\r\nPOST /api HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nConnection: close\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.get("host"), Some("x"));
+        assert_eq!(request.framing(), Framing::Length(5));
+        assert!(request.close);
+
+        let response = head("HTTP/1.1 204 No Content\ntransfer-encoding: gzip\n\n")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(response.status(), Some(204));
+        assert_eq!(response.framing(), Framing::Other);
+
+        assert!(head("").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_ambiguous_heads() {
+        for raw in [
+            "POST / HTTP/1.1\r\ncontent-length: 5\r\ncontent-length: 5\r\n\r\n",
+            "POST / HTTP/1.1\r\ncontent-length: 5\r\ncontent-length: 6\r\n\r\n",
+            "POST / HTTP/1.1\r\ncontent-length: +5\r\n\r\n",
+            "POST / HTTP/1.1\r\ncontent-length: 5, 5\r\n\r\n",
+            "POST / HTTP/1.1\r\ncontent-length: 5\r\ntransfer-encoding: chunked\r\n\r\n",
+            "POST / HTTP/1.1\r\ncontent-length : 5\r\n\r\n",
+            "POST / HTTP/1.1\r\nhost: x\r\n content-length: 5\r\n\r\n",
+            "POST / HTTP/1.1\r\nhost: x\rcontent-length: 5\r\n\r\n",
+            "POST / HTTP/1.1\r\nhost\r\n\r\n",
+            "POST / HTTP/1.1\r\nhost: x\r\n",
+        ] {
+            let error = head(raw).err().expect(raw);
+
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{raw}");
+        }
+
+        let large = format!("GET / HTTP/1.1\r\nx: {}\r\n\r\n", "a".repeat(MAX_HEAD));
+
+        assert!(head(&large).is_err());
+    }
+
+    #[test]
+    fn relays_chunked_and_continued_bodies_over_one_connection() {
+        let (backend, connections) = backend(false);
+
+        let output = relay(
+            &backend,
+            "POST /a HTTP/1.1\r\nexpect: 100-continue\r\ntransfer-encoding: chunked\r\nx-kitpanel-peer: 6.6.6.6\r\n\r\n\
+             5\r\nhello\r\n6;name=value\r\n world\r\n0\r\ntrailer: x\r\n\r\n\
+             GET /b HTTP/1.1\r\n\r\n",
+        );
+
+        assert!(output.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+        assert!(output.contains("\r\n\r\nPOST /a HTTP/1.1 10.0.0.1 hello worldHTTP/1.1 200 OK\r\n"));
+        assert!(output.ends_with("\r\n\r\nGET /b HTTP/1.1 10.0.0.1 "));
+
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reconnects_when_the_backend_closes() {
+        let (backend, connections) = backend(true);
+
+        let output = relay(
+            &backend,
+            "POST /a HTTP/1.1\r\ncontent-length: 2\r\n\r\nhiGET /b HTTP/1.1\r\n\r\n",
+        );
+
+        assert!(output.contains("\r\n\r\nPOST /a HTTP/1.1 10.0.0.1 hiHTTP/1.1 200 OK\r\n"));
+        assert!(output.ends_with("\r\n\r\nGET /b HTTP/1.1 10.0.0.1 "));
+
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn rejects_requests_it_cannot_frame() {
+        let (backend, connections) = backend(false);
+
+        for (request, status) in [
+            (
+                "POST / HTTP/1.1\r\ncontent-length: 1\r\ncontent-length: 2\r\n\r\nab",
+                "400",
+            ),
+            (
+                "POST / HTTP/1.1\r\ntransfer-encoding: gzip, chunked\r\n\r\n",
+                "501",
+            ),
+            (
+                "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\nzz\r\n",
+                "400",
+            ),
+            (
+                "POST / HTTP/1.1\r\nexpect: magic\r\ncontent-length: 1\r\n\r\na",
+                "417",
+            ),
+        ] {
+            let output = relay(&backend, request);
+
+            assert!(
+                output.starts_with(&format!("HTTP/1.1 {} ", status)),
+                "{request:?} got {output:?}"
+            );
+        }
+
+        let large = format!(
+            "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n{:x}\r\n",
+            MAX_BODY + 1
+        );
+
+        assert!(relay(&backend, &large).starts_with("HTTP/1.1 413 "));
+
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+    }
+}
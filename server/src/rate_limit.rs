@@ -0,0 +1,300 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use foxhole::{
+    resolve::{Resolve, ResolveGuard},
+    type_cache::TypeCacheKey,
+    IntoResponse, PathIter, RequestState,
+};
+use models::{LockoutInfo, LockoutKind};
+
+use crate::authentication::User;
+
+const FREE_ATTEMPTS: u32 = 5;
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(3600);
+const FORGET_AFTER: Duration = Duration::from_secs(3600);
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl Failures {
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.locked_until.filter(|i| *i > now).map(|i| i - now)
+    }
+
+    fn lock(&mut self, now: Instant) {
+        self.locked_until = self
+            .count
+            .checked_sub(FREE_ATTEMPTS)
+            .map(|exponent| now + (BASE_LOCKOUT * 2u32.pow(exponent.min(16))).min(MAX_LOCKOUT));
+    }
+}
+
+#[derive(Default)]
+pub struct LoginGuard {
+    failures: HashMap<(LockoutKind, String), Failures>,
+}
+
+impl TypeCacheKey for LoginGuard {
+    type Value = Arc<RwLock<LoginGuard>>;
+}
+
+impl LoginGuard {
+    /// Starts a login attempt, counting it as failed until
+    /// [`LoginGuard::record_success`] says otherwise, so concurrent attempts
+    /// cannot all pass the check before any failure is recorded.
+    pub fn attempt(&mut self, username: &str, ip: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        self.failures
+            .retain(|_, i| i.remaining(now).is_some() || now - i.last < FORGET_AFTER);
+
+        let remaining = [(LockoutKind::User, username), (LockoutKind::Ip, ip)]
+            .into_iter()
+            .filter_map(|(kind, key)| self.failures.get(&(kind, key.to_string())))
+            .filter_map(|i| i.remaining(now))
+            .max();
+
+        if let Some(remaining) = remaining {
+            return Err(remaining);
+        }
+
+        for key in [(LockoutKind::User, username), (LockoutKind::Ip, ip)] {
+            let failures = self
+                .failures
+                .entry((key.0, key.1.to_string()))
+                .or_insert(Failures {
+                    count: 0,
+                    last: now,
+                    locked_until: None,
+                });
+
+            failures.count += 1;
+            failures.last = now;
+            failures.lock(now);
+        }
+
+        Ok(())
+    }
+
+    /// Takes back the failure counted by [`LoginGuard::attempt`]. The attempt
+    /// was only let through while nothing was locked, so neither is now.
+    pub fn record_success(&mut self, username: &str, ip: &str) {
+        self.failures
+            .remove(&(LockoutKind::User, username.to_string()));
+
+        let key = (LockoutKind::Ip, ip.to_string());
+
+        if let Some(failures) = self.failures.get_mut(&key) {
+            failures.count = failures.count.saturating_sub(1);
+            failures.locked_until = None;
+
+            if failures.count == 0 {
+                self.failures.remove(&key);
+            }
+        }
+    }
+
+    pub fn lockouts(&self) -> Vec<LockoutInfo> {
+        let now = Instant::now();
+
+        self.failures
+            .iter()
+            .filter_map(|((kind, key), failures)| {
+                Some(LockoutInfo {
+                    kind: *kind,
+                    key: key.clone(),
+                    failures: failures.count,
+                    remaining: failures.remaining(now)?.as_secs(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self, kind: LockoutKind, key: &str) -> bool {
+        self.failures.remove(&(kind, key.to_string())).is_some()
+    }
+}
+
+pub trait Rate {
+    const ACTION: &'static str;
+    const CAPACITY: f64;
+    const PER_SECOND: f64;
+}
+
+pub struct Power;
+
+impl Rate for Power {
+    const ACTION: &'static str = "power";
+    const CAPACITY: f64 = 5.0;
+    const PER_SECOND: f64 = 0.5;
+}
+
+pub struct Commands;
+
+impl Rate for Commands {
+    const ACTION: &'static str = "input";
+    const CAPACITY: f64 = 20.0;
+    const PER_SECOND: f64 = 5.0;
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: HashMap<(&'static str, String), Bucket>,
+}
+
+impl TypeCacheKey for RateLimiter {
+    type Value = Arc<RwLock<RateLimiter>>;
+}
+
+impl RateLimiter {
+    fn allow<R: Rate>(&mut self, user_id: &str) -> bool {
+        let now = Instant::now();
+
+        let bucket = self
+            .buckets
+            .entry((R::ACTION, user_id.to_string()))
+            .or_insert(Bucket {
+                tokens: R::CAPACITY,
+                last: now,
+            });
+
+        let elapsed = (now - bucket.last).as_secs_f64();
+
+        bucket.tokens = (bucket.tokens + elapsed * R::PER_SECOND).min(R::CAPACITY);
+        bucket.last = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+
+        true
+    }
+}
+
+pub struct Throttle<R>(PhantomData<R>);
+
+impl<'a, R> Resolve<'a> for Throttle<R>
+where
+    R: 'a + Rate,
+{
+    type Output = Throttle<R>;
+
+    fn resolve(ctx: &'a RequestState, path_iter: &mut PathIter) -> ResolveGuard<Self::Output> {
+        let user = match User::resolve(ctx, path_iter) {
+            ResolveGuard::Value(user) => user,
+            ResolveGuard::Respond(response) => return ResolveGuard::Respond(response),
+            ResolveGuard::None => return ResolveGuard::None,
+        };
+
+        let cache = ctx.global_cache.read().unwrap();
+
+        let mut limiter = cache.get::<RateLimiter>().unwrap().write().unwrap();
+
+        match limiter.allow::<R>(&user.user_id) {
+            true => ResolveGuard::Value(Throttle(PhantomData)),
+            false => ResolveGuard::Respond(429u16.response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_out_after_free_attempts() {
+        let mut guard = LoginGuard::default();
+
+        for _ in 0..FREE_ATTEMPTS - 1 {
+            assert!(guard.attempt("admin", "10.0.0.1").is_ok());
+        }
+
+        assert!(guard.attempt("admin", "10.0.0.1").is_ok());
+
+        let remaining = guard.attempt("admin", "10.0.0.2").unwrap_err();
+
+        assert!(remaining <= BASE_LOCKOUT && remaining > BASE_LOCKOUT - Duration::from_secs(5));
+
+        let remaining = guard.attempt("other", "10.0.0.1").unwrap_err();
+
+        assert!(remaining <= BASE_LOCKOUT);
+
+        assert!(guard.attempt("other", "10.0.0.2").is_ok());
+    }
+
+    #[test]
+    fn doubles_the_lockout() {
+        let mut failures = Failures {
+            count: FREE_ATTEMPTS,
+            last: Instant::now(),
+            locked_until: None,
+        };
+
+        let now = Instant::now();
+
+        let lockouts: Vec<Duration> = (0..4)
+            .map(|_| {
+                failures.lock(now);
+                failures.count += 1;
+                failures.remaining(now).unwrap()
+            })
+            .collect();
+
+        assert_eq!(lockouts, [1, 2, 4, 8].map(|i| BASE_LOCKOUT * i),);
+
+        failures.count = FREE_ATTEMPTS + 100;
+        failures.lock(now);
+
+        assert_eq!(failures.remaining(now), Some(MAX_LOCKOUT));
+    }
+
+    #[test]
+    fn success_takes_back_the_attempt() {
+        let mut guard = LoginGuard::default();
+
+        for _ in 0..FREE_ATTEMPTS - 1 {
+            guard.attempt("admin", "10.0.0.1").unwrap();
+        }
+
+        guard.attempt("admin", "10.0.0.1").unwrap();
+
+        guard.record_success("admin", "10.0.0.1");
+
+        assert!(guard.lockouts().is_empty());
+
+        assert!(guard.attempt("admin", "10.0.0.1").is_ok());
+    }
+
+    #[test]
+    fn clears_lockouts() {
+        let mut guard = LoginGuard::default();
+
+        for _ in 0..FREE_ATTEMPTS {
+            guard.attempt("admin", "10.0.0.1").unwrap();
+        }
+
+        assert_eq!(guard.lockouts().len(), 2);
+
+        assert!(guard.clear(LockoutKind::User, "admin"));
+        assert!(!guard.clear(LockoutKind::User, "admin"));
+
+        assert!(guard.attempt("admin", "10.0.0.2").is_ok());
+        assert!(guard.attempt("admin", "10.0.0.1").is_err());
+    }
+}
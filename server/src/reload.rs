@@ -0,0 +1,278 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::ErrorKind,
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    authentication::Authentication, check, fs::Config, process::ProcessManager,
+    server_config::ServerConfig,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const RESTART_FIELDS: [&str; 4] = ["address", "port", "tls_cert", "tls_key"];
+
+pub fn watch(
+    config: Arc<RwLock<ServerConfig>>,
+    authentication: Arc<RwLock<Authentication>>,
+    processes: Arc<RwLock<ProcessManager>>,
+) {
+    let mut servers = Watched::<ServerConfig>::new();
+    let mut accounts = Watched::<Authentication>::new();
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        servers.poll(&config, || reload_servers(&config, &processes));
+
+        accounts.poll(&authentication, || {
+            reload_accounts(&authentication, &config)
+        });
+    }
+}
+
+struct Watched<T> {
+    modified: Option<SystemTime>,
+    error: Option<String>,
+    config: PhantomData<T>,
+}
+
+impl<T: Config> Watched<T> {
+    fn new() -> Self {
+        Self {
+            modified: modified::<T>(),
+            error: None,
+            config: PhantomData,
+        }
+    }
+
+    /// Reloads the file if it changed since the last poll, unless it holds
+    /// exactly what the panel itself last saved. A file that fails to parse,
+    /// possibly because it is still being written, is retried on every poll
+    /// until it changes or parses.
+    fn poll(&mut self, current: &RwLock<T>, reload: impl FnOnce() -> Result<(), String>) {
+        let modified = modified::<T>();
+
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+
+        self.modified = modified;
+
+        if std::fs::read(T::full_path()).ok() == Some(current.read().unwrap().bytes()) {
+            self.error = None;
+            return;
+        }
+
+        match reload() {
+            Ok(()) => self.error = None,
+            Err(e) => {
+                if self.error.as_ref() != Some(&e) {
+                    println!("Failed to reload {:?}: {e}", T::full_path());
+                }
+
+                self.error = Some(e);
+                self.modified = None;
+            }
+        }
+    }
+}
+
+fn modified<T: Config>() -> Option<SystemTime> {
+    std::fs::metadata(T::full_path())
+        .and_then(|i| i.modified())
+        .ok()
+}
+
+/// Reads a config without writing anything back, unlike `Config::get`. The
+/// migrations only apply in memory until the panel next saves the file, and a
+/// file that disappeared keeps the current config.
+fn read<T: Config>() -> Result<Option<T>, String> {
+    match T::read() {
+        Ok(mut config) => {
+            config.migrate();
+
+            Ok(Some(config))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn reload_servers(
+    config: &RwLock<ServerConfig>,
+    processes: &RwLock<ProcessManager>,
+) -> Result<(), String> {
+    let alive: HashSet<String> = processes
+        .read()
+        .unwrap()
+        .0
+        .iter()
+        .filter(|(_, process)| process.is_alive())
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    let mut config = config.write().unwrap();
+
+    let Some(mut updated) = read::<ServerConfig>()? else {
+        return Ok(());
+    };
+
+    if let Err(errors) = updated.validate() {
+        println!("Ignoring invalid servers.json: {}", errors.join("; "));
+        return Ok(());
+    }
+
+    check::warn("servers.json", updated.warnings());
+
+    for server in config.servers.iter() {
+        if alive.contains(&server.id) && !updated.servers.iter().any(|i| i.id == server.id) {
+            println!(
+                "Keeping running server {} until it is stopped and removed",
+                server.id
+            );
+
+            updated.servers.push(server.clone());
+        }
+    }
+
+    let mut changes = diff(
+        keyed(config.servers.iter().map(|i| (i.id.clone(), i))),
+        keyed(updated.servers.iter().map(|i| (i.id.clone(), i))),
+    );
+
+    let (old, new) = (to_value(&*config), to_value(&updated));
+
+    for (field, value) in new.as_object().into_iter().flatten() {
+        if field == "servers" || old.get(field) == Some(value) {
+            continue;
+        }
+
+        match RESTART_FIELDS.contains(&field.as_str()) {
+            true => changes.push(format!("~ {field} (takes effect after a restart)")),
+            false => changes.push(format!("~ {field}")),
+        }
+    }
+
+    *config = updated;
+
+    report("servers.json", changes);
+
+    Ok(())
+}
+
+fn reload_accounts(
+    authentication: &RwLock<Authentication>,
+    config: &RwLock<ServerConfig>,
+) -> Result<(), String> {
+    let config = config.read().unwrap().clone();
+
+    let mut authentication = authentication.write().unwrap();
+
+    let Some(mut updated) = read::<Authentication>()? else {
+        return Ok(());
+    };
+
+    if let Err(errors) = updated.validate() {
+        println!("Ignoring invalid accounts.json: {}", errors.join("; "));
+        return Ok(());
+    }
+
+    check::warn("accounts.json", updated.warnings(&config));
+
+    updated.carry_over(&mut authentication);
+
+    let changes = diff(
+        keyed(authentication.users.iter().map(|(k, v)| (k.clone(), v))),
+        keyed(updated.users.iter().map(|(k, v)| (k.clone(), v))),
+    );
+
+    *authentication = updated;
+
+    report("accounts.json", changes);
+
+    Ok(())
+}
+
+fn to_value(value: &impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn keyed<'a, T: Serialize + 'a>(
+    items: impl Iterator<Item = (String, &'a T)>,
+) -> BTreeMap<String, Value> {
+    items.map(|(k, v)| (k, to_value(v))).collect()
+}
+
+fn diff(old: BTreeMap<String, Value>, new: BTreeMap<String, Value>) -> Vec<String> {
+    let mut changes = vec![];
+
+    for (key, value) in new.iter() {
+        match old.get(key) {
+            None => changes.push(format!("+ {key}")),
+            Some(previous) if previous != value => changes.push(format!("~ {key}")),
+            _ => {}
+        }
+    }
+
+    for key in old.keys().filter(|i| !new.contains_key(*i)) {
+        changes.push(format!("- {key}"));
+    }
+
+    changes
+}
+
+fn report(file: &str, changes: Vec<String>) {
+    if changes.is_empty() {
+        return;
+    }
+
+    println!("Reloaded {file}:");
+
+    for change in changes {
+        println!("  {change}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn map(items: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn reports_added_changed_and_removed() {
+        let old = map(&[
+            ("kept", json!(1)),
+            ("changed", json!({ "a": 1 })),
+            ("removed", json!(null)),
+        ]);
+
+        let new = map(&[
+            ("kept", json!(1)),
+            ("changed", json!({ "a": 2 })),
+            ("added", json!([])),
+        ]);
+
+        assert_eq!(diff(old, new), ["+ added", "~ changed", "- removed"]);
+    }
+
+    #[test]
+    fn reports_nothing_when_unchanged() {
+        let items = map(&[("a", json!("x")), ("b", json!(2))]);
+
+        assert!(diff(items.clone(), items).is_empty());
+    }
+}
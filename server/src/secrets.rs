@@ -0,0 +1,100 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use foxhole::type_cache::TypeCacheKey;
+use serde::{Deserialize, Serialize};
+
+use crate::fs::Config;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Secrets {
+    #[serde(default)]
+    pub global: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub servers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config for Secrets {
+    const PRIVATE: bool = true;
+
+    fn rel_path(rel: PathBuf) -> PathBuf {
+        rel.join("secrets.json")
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+}
+
+impl TypeCacheKey for Secrets {
+    type Value = Arc<RwLock<Secrets>>;
+}
+
+impl Secrets {
+    pub fn for_server(&self, server_id: &str) -> BTreeMap<String, String> {
+        let mut env = self.global.clone();
+
+        if let Some(server) = self.servers.get(server_id) {
+            env.extend(server.clone());
+        }
+
+        env
+    }
+
+    /// The values hidden from a server's console, logs and audit entries.
+    pub fn redactions(&self, server_id: &str) -> Vec<String> {
+        self.for_server(server_id)
+            .into_values()
+            .filter(|i| !i.is_empty())
+            .collect()
+    }
+
+    pub fn redact(&self, server_id: &str, content: String) -> String {
+        redact(&self.redactions(server_id), content)
+    }
+}
+
+pub fn redact(secrets: &[String], mut content: String) -> String {
+    for secret in secrets.iter() {
+        if content.contains(secret.as_str()) {
+            content = content.replace(secret.as_str(), "********");
+        }
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_secrets_override_global_ones() {
+        let secrets = Secrets {
+            global: BTreeMap::from([
+                ("TOKEN".to_string(), "global".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]),
+            servers: BTreeMap::from([(
+                "alpha".to_string(),
+                BTreeMap::from([("TOKEN".to_string(), "alpha".to_string())]),
+            )]),
+        };
+
+        assert_eq!(secrets.for_server("alpha")["TOKEN"], "alpha");
+        assert_eq!(secrets.for_server("beta")["TOKEN"], "global");
+        assert_eq!(secrets.redactions("alpha"), ["alpha"]);
+        assert_eq!(
+            secrets.redact("beta", "token=global".to_string()),
+            "token=********"
+        );
+    }
+}
@@ -1,18 +1,154 @@
 use std::{
+    collections::{BTreeMap, HashSet},
+    net::IpAddr,
     path::PathBuf,
     sync::{Arc, RwLock},
 };
 
 use foxhole::type_cache::TypeCacheKey;
+use models::{ServerDefinition, ServerUpdate};
 use serde::{Deserialize, Serialize};
 
-use crate::fs::Config;
+use crate::{fs::Config, limits::Limits, log::LogConfig};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ServerInfo {
     pub id: String,
     pub display: String,
-    pub start_command: String,
+    pub start_command: StartCommand,
+
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    #[serde(default = "ServerInfo::default_scrollback")]
+    pub scrollback: usize,
+
+    #[serde(default)]
+    pub stop_command: Option<String>,
+
+    #[serde(default)]
+    pub stop_signal: Option<StopSignal>,
+
+    #[serde(default = "ServerInfo::default_stop_timeout")]
+    pub stop_timeout: u64,
+
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+
+    #[serde(default)]
+    pub limits: Option<Limits>,
+
+    #[serde(default)]
+    pub detached: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartMode {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    pub max_retries: u32,
+    pub backoff: u64,
+    pub window: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            mode: RestartMode::Never,
+            max_retries: 5,
+            backoff: 5,
+            window: 300,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum StartCommand {
+    Legacy(String),
+    Spec(CommandSpec),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CommandSpec {
+    pub program: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+
+    #[serde(default)]
+    pub clear_env: bool,
+}
+
+impl StartCommand {
+    pub fn spec(&self) -> Result<CommandSpec, String> {
+        let spec = match self {
+            StartCommand::Legacy(command) => {
+                let mut words = shell_words::split(command)
+                    .map_err(|e| format!("Failed to parse start command: {}", e))?
+                    .into_iter();
+
+                CommandSpec {
+                    program: words.next().unwrap_or_default(),
+                    args: words.collect(),
+                    ..Default::default()
+                }
+            }
+            StartCommand::Spec(spec) => spec.clone(),
+        };
+
+        if spec.program.trim().is_empty() {
+            return Err("Start command is empty".to_string());
+        }
+
+        Ok(spec)
+    }
+
+    pub fn command_line(&self) -> String {
+        match self {
+            StartCommand::Legacy(command) => command.clone(),
+            StartCommand::Spec(spec) => {
+                shell_words::join(std::iter::once(&spec.program).chain(spec.args.iter()))
+            }
+        }
+    }
+
+    pub fn set_command_line(&mut self, line: String) -> Result<(), String> {
+        let parsed = StartCommand::Legacy(line.clone()).spec()?;
+
+        match self {
+            StartCommand::Legacy(command) => *command = line,
+            StartCommand::Spec(spec) => {
+                spec.program = parsed.program;
+                spec.args = parsed.args;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum StopSignal {
+    #[serde(rename = "SIGTERM")]
+    Term,
+    #[serde(rename = "SIGINT")]
+    Int,
 }
 
 impl ServerInfo {
@@ -20,9 +156,66 @@ impl ServerInfo {
         Self {
             id: "example".to_string(),
             display: "Example".to_string(),
-            start_command: "example start command".to_string(),
+            start_command: StartCommand::Legacy("example start command".to_string()),
+            env: BTreeMap::new(),
+            scrollback: Self::default_scrollback(),
+            stop_command: None,
+            stop_signal: None,
+            stop_timeout: Self::default_stop_timeout(),
+            restart_policy: RestartPolicy::default(),
+            limits: None,
+            detached: false,
+        }
+    }
+
+    pub fn from_definition(definition: ServerDefinition) -> Result<Self, String> {
+        validate_id(&definition.id)?;
+
+        let start_command = StartCommand::Legacy(definition.start_command);
+
+        start_command.spec()?;
+
+        Ok(Self {
+            id: definition.id,
+            display: definition.display,
+            start_command,
+            env: definition.env,
+            ..Self::template()
+        })
+    }
+
+    pub fn definition(&self) -> ServerDefinition {
+        ServerDefinition {
+            id: self.id.clone(),
+            display: self.display.clone(),
+            start_command: self.start_command.command_line(),
+            env: self.env.clone(),
         }
     }
+
+    pub fn apply(&mut self, update: ServerUpdate) -> Result<(), String> {
+        if let Some(start_command) = update.start_command {
+            self.start_command.set_command_line(start_command)?;
+        }
+
+        if let Some(display) = update.display {
+            self.display = display;
+        }
+
+        if let Some(env) = update.env {
+            self.env = env;
+        }
+
+        Ok(())
+    }
+
+    pub fn default_scrollback() -> usize {
+        1000
+    }
+
+    fn default_stop_timeout() -> u64 {
+        30
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,6 +224,20 @@ pub struct ServerConfig {
     pub port: String,
     pub server_directory: PathBuf,
     pub servers: Vec<ServerInfo>,
+
+    #[serde(default)]
+    pub logging: LogConfig,
+
+    #[serde(default)]
+    pub tls_cert: Option<PathBuf>,
+
+    #[serde(default)]
+    pub tls_key: Option<PathBuf>,
+
+    /// Reverse proxies whose `X-Forwarded-For` and `X-Real-IP` headers are
+    /// trusted to name the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl Default for ServerConfig {
@@ -40,6 +247,10 @@ impl Default for ServerConfig {
             port: "8080".to_string(),
             server_directory: ServerConfig::server_dir(),
             servers: vec![ServerInfo::template()],
+            logging: LogConfig::default(),
+            tls_cert: None,
+            tls_key: None,
+            trusted_proxies: vec![],
         }
     }
 }
@@ -53,8 +264,8 @@ impl Config for ServerConfig {
         serde_json::to_vec_pretty(self).unwrap()
     }
 
-    fn from_bytes(bytes: &[u8]) -> Option<Self> {
-        serde_json::from_slice(bytes).ok()
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
     }
 }
 
@@ -62,8 +273,170 @@ impl TypeCacheKey for ServerConfig {
     type Value = Arc<RwLock<ServerConfig>>;
 }
 
+fn validate_id(id: &str) -> Result<(), String> {
+    let valid = id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    match !id.is_empty() && valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid server id {:?}, use only letters, digits, '-' and '_'",
+            id
+        )),
+    }
+}
+
 impl ServerConfig {
     fn server_dir() -> PathBuf {
-        Self::full_path().parent().unwrap().to_path_buf().join("servers")
+        Self::full_path()
+            .parent()
+            .unwrap()
+            .to_path_buf()
+            .join("servers")
+    }
+
+    pub fn ids(&self) -> HashSet<&str> {
+        self.servers.iter().map(|i| i.id.as_str()).collect()
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+
+        if !matches!(self.port.parse::<u16>(), Ok(1..)) {
+            errors.push(format!("Invalid port {:?}", self.port));
+        }
+
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            errors.push("tls_cert and tls_key must be set together".to_string());
+        }
+
+        let mut ids = HashSet::new();
+
+        for server in self.servers.iter() {
+            if !ids.insert(server.id.as_str()) {
+                errors.push(format!("Duplicate server id {:?}", server.id));
+            }
+
+            if let Err(e) = server.start_command.spec() {
+                errors.push(format!("Server {:?}: {}", server.id, e));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Ids from before they were restricted still work, only new servers
+    /// are held to the current rules.
+    pub fn warnings(&self) -> Vec<String> {
+        self.servers
+            .iter()
+            .filter_map(|i| validate_id(&i.id).err())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(id: &str, command: &str) -> ServerInfo {
+        ServerInfo {
+            id: id.to_string(),
+            start_command: StartCommand::Legacy(command.to_string()),
+            ..ServerInfo::template()
+        }
+    }
+
+    fn config(servers: Vec<ServerInfo>) -> ServerConfig {
+        ServerConfig {
+            servers,
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn splits_legacy_commands() {
+        let spec = StartCommand::Legacy("java -Xmx2G -jar 'server one.jar' nogui".to_string())
+            .spec()
+            .unwrap();
+
+        assert_eq!(spec.program, "java");
+        assert_eq!(spec.args, ["-Xmx2G", "-jar", "server one.jar", "nogui"]);
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert!(StartCommand::Legacy("   ".to_string()).spec().is_err());
+        assert!(StartCommand::Legacy("java 'unterminated".to_string())
+            .spec()
+            .is_err());
+        assert!(StartCommand::Spec(CommandSpec::default()).spec().is_err());
+    }
+
+    #[test]
+    fn reads_both_command_forms() {
+        let legacy: StartCommand = serde_json::from_str(r#""./run.sh --port 1""#).unwrap();
+
+        assert!(matches!(legacy, StartCommand::Legacy(_)));
+
+        let spec: StartCommand =
+            serde_json::from_str(r#"{ "program": "./run.sh", "args": ["--port", "1"] }"#).unwrap();
+
+        assert_eq!(spec.spec().unwrap().args, ["--port", "1"]);
+        assert_eq!(spec.command_line(), "./run.sh --port 1");
+    }
+
+    #[test]
+    fn keeps_the_command_form_when_changed() {
+        let mut command = StartCommand::Spec(CommandSpec {
+            program: "old".to_string(),
+            clear_env: true,
+            ..Default::default()
+        });
+
+        command.set_command_line("new --flag".to_string()).unwrap();
+
+        let StartCommand::Spec(spec) = command else {
+            panic!("command was converted to a legacy command");
+        };
+
+        assert_eq!(spec.program, "new");
+        assert_eq!(spec.args, ["--flag"]);
+        assert!(spec.clear_env);
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let config = ServerConfig::default();
+
+        assert!(config.validate().is_ok());
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        let mut config = config(vec![
+            server("alpha", "./run"),
+            server("alpha", "./run"),
+            server("beta", ""),
+        ]);
+
+        config.port = "0".to_string();
+        config.tls_cert = Some(PathBuf::from("cert.pem"));
+
+        assert_eq!(config.validate().unwrap_err().len(), 4);
+    }
+
+    #[test]
+    fn only_warns_about_legacy_ids() {
+        let config = config(vec![server("My Server", "./run")]);
+
+        assert!(config.validate().is_ok());
+        assert_eq!(config.warnings().len(), 1);
+        assert!(ServerInfo::from_definition(config.servers[0].definition()).is_err());
     }
 }
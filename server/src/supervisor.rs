@@ -0,0 +1,248 @@
+use std::{
+    process::ExitStatus,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use models::Stream;
+
+use crate::{
+    process::{Process, ProcessManager, RestartState},
+    secrets::Secrets,
+    server_config::{RestartMode, RestartPolicy, ServerConfig},
+};
+
+const TICK: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+pub fn supervise(
+    config: Arc<RwLock<ServerConfig>>,
+    secrets: Arc<RwLock<Secrets>>,
+    processes: Arc<RwLock<ProcessManager>>,
+) {
+    loop {
+        std::thread::sleep(TICK);
+
+        let mut processes = processes.write().unwrap();
+
+        let ids: Vec<String> = processes.0.keys().cloned().collect();
+
+        let mut reaped = false;
+
+        for id in ids {
+            let Some(process) = processes.0.get_mut(&id) else {
+                continue;
+            };
+
+            if let Some(status) = process.reap() {
+                reaped = true;
+
+                let config = config.read().unwrap();
+
+                let policy = config
+                    .servers
+                    .iter()
+                    .find(|i| i.id == id)
+                    .map(|i| &i.restart_policy);
+
+                if let Some(policy) = policy {
+                    if !process.is_stopping() && should_restart(policy, status) {
+                        schedule(process, policy, status);
+                    }
+                }
+
+                continue;
+            }
+
+            let Some(due) = process.restart.pending else {
+                continue;
+            };
+
+            if Instant::now() < due {
+                continue;
+            }
+
+            process.restart.pending = None;
+
+            let config = config.read().unwrap();
+
+            let Some(server) = config.servers.iter().find(|i| i.id == id) else {
+                continue;
+            };
+
+            let result = processes.start(&config, server, &secrets.read().unwrap());
+
+            let Some(process) = processes.0.get_mut(&id) else {
+                continue;
+            };
+
+            match result {
+                Ok(()) => {
+                    process.restart.count += 1;
+                    process.restart.recent.push_back(Instant::now());
+
+                    let message = format!(
+                        "[KitPanel] Restarted server (restart #{})",
+                        process.restart.count
+                    );
+
+                    process.console.push(Stream::Panel, message);
+                }
+                Err(e) => {
+                    let message = format!("[KitPanel] Failed to restart server: {}", e);
+
+                    process.console.push(Stream::Panel, message);
+                }
+            }
+        }
+
+        if reaped {
+            processes.persist();
+        }
+    }
+}
+
+fn should_restart(policy: &RestartPolicy, status: ExitStatus) -> bool {
+    match policy.mode {
+        RestartMode::Never => false,
+        RestartMode::OnFailure => !status.success(),
+        RestartMode::Always => true,
+    }
+}
+
+/// Forgets restarts older than the policy window, then returns how many
+/// remain and how long to wait before the next one, or `None` to give up.
+fn backoff(
+    restart: &mut RestartState,
+    policy: &RestartPolicy,
+    now: Instant,
+) -> (u32, Option<Duration>) {
+    let window = Duration::from_secs(policy.window);
+
+    let recent = &mut restart.recent;
+
+    while recent.front().is_some_and(|i| now - *i > window) {
+        recent.pop_front();
+    }
+
+    let attempt = recent.len() as u32;
+
+    if attempt >= policy.max_retries {
+        return (attempt, None);
+    }
+
+    let delay =
+        Duration::from_secs(policy.backoff.saturating_mul(1 << attempt.min(16))).min(MAX_BACKOFF);
+
+    (attempt, Some(delay))
+}
+
+fn schedule(process: &mut Process, policy: &RestartPolicy, status: ExitStatus) {
+    let now = Instant::now();
+
+    let (attempt, delay) = backoff(&mut process.restart, policy, now);
+
+    let Some(delay) = delay else {
+        let message = format!(
+            "[KitPanel] Server exited ({}) after {} restarts within {}s, giving up",
+            status, attempt, policy.window
+        );
+
+        process.console.push(Stream::Panel, message);
+
+        return;
+    };
+
+    process.restart.pending = Some(now + delay);
+
+    let message = format!(
+        "[KitPanel] Server exited ({}), restarting in {}s (attempt {}/{})",
+        status,
+        delay.as_secs(),
+        attempt + 1,
+        policy.max_retries
+    );
+
+    process.console.push(Stream::Panel, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy {
+            mode: RestartMode::OnFailure,
+            max_retries: 3,
+            backoff: 5,
+            window: 60,
+        }
+    }
+
+    #[test]
+    fn doubles_the_delay_per_recent_restart() {
+        let (policy, now) = (policy(), Instant::now());
+
+        let mut restart = RestartState::default();
+
+        let mut delays = vec![];
+
+        while let (_, Some(delay)) = backoff(&mut restart, &policy, now) {
+            delays.push(delay.as_secs());
+            restart.recent.push_back(now);
+        }
+
+        assert_eq!(delays, [5, 10, 20]);
+    }
+
+    #[test]
+    fn forgets_restarts_outside_the_window() {
+        let (policy, now) = (policy(), Instant::now() + Duration::from_secs(3600));
+
+        let mut restart = RestartState::default();
+
+        restart.recent.extend([
+            now - Duration::from_secs(120),
+            now - Duration::from_secs(90),
+            now - Duration::from_secs(10),
+        ]);
+
+        assert_eq!(
+            backoff(&mut restart, &policy, now),
+            (1, Some(Duration::from_secs(10)))
+        );
+        assert_eq!(restart.recent.len(), 1);
+    }
+
+    #[test]
+    fn caps_the_delay() {
+        let policy = RestartPolicy {
+            max_retries: 100,
+            backoff: 60,
+            ..policy()
+        };
+
+        let now = Instant::now();
+
+        let mut restart = RestartState::default();
+
+        restart.recent.extend([now; 10]);
+
+        assert_eq!(backoff(&mut restart, &policy, now).1, Some(MAX_BACKOFF));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restarts_by_mode() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let (success, failure) = (ExitStatus::from_raw(0), ExitStatus::from_raw(1 << 8));
+
+        let policy = |mode| RestartPolicy { mode, ..policy() };
+
+        assert!(!should_restart(&policy(RestartMode::Never), failure));
+        assert!(!should_restart(&policy(RestartMode::OnFailure), success));
+        assert!(should_restart(&policy(RestartMode::OnFailure), failure));
+        assert!(should_restart(&policy(RestartMode::Always), success));
+    }
+}
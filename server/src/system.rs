@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+use foxhole::type_cache::TypeCacheKey;
+use models::SystemOverview;
+
+pub struct Panel {
+    started: Instant,
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+
+    pub fn overview(&self, directory: &Path) -> SystemOverview {
+        let (memory_total, memory_available) = read_memory().unzip();
+        let (disk_total, disk_available) = disk_usage(directory).unzip();
+
+        SystemOverview {
+            load_average: read_load_average(),
+            memory_total,
+            memory_available,
+            disk_total,
+            disk_available,
+            uptime: self.started.elapsed().as_secs(),
+        }
+    }
+}
+
+impl TypeCacheKey for Panel {
+    type Value = Arc<RwLock<Panel>>;
+}
+
+fn read_load_average() -> Option<[f32; 3]> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+
+    let mut fields = loadavg.split_whitespace().map(|i| i.parse().ok());
+
+    Some([fields.next()??, fields.next()??, fields.next()??])
+}
+
+fn read_memory() -> Option<(u64, u64)> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+
+    let field = |name: &str| -> Option<u64> {
+        let kb: u64 = meminfo
+            .lines()
+            .find_map(|i| i.strip_prefix(name))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()?;
+
+        Some(kb * 1024)
+    };
+
+    Some((field("MemTotal:")?, field("MemAvailable:")?))
+}
+
+#[cfg(unix)]
+fn disk_usage(directory: &Path) -> Option<(u64, u64)> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(directory.as_os_str().as_bytes()).ok()?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    let block = stat.f_frsize as u64;
+
+    Some((stat.f_blocks as u64 * block, stat.f_bavail as u64 * block))
+}
+
+#[cfg(not(unix))]
+fn disk_usage(_directory: &Path) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,68 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rustls::ServerConfig;
+use sha2::{Digest, Sha256};
+
+fn resolve(path: &Path) -> PathBuf {
+    std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join(path)
+}
+
+pub fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e)
+}
+
+fn generate(cert: &Path, key: &Path, address: &str) -> io::Result<()> {
+    let mut names = vec!["localhost".to_string()];
+
+    if address != "0.0.0.0" {
+        names.push(address.to_string());
+    }
+
+    let generated = rcgen::generate_simple_self_signed(names).map_err(invalid)?;
+
+    fs::write(cert, generated.serialize_pem().map_err(invalid)?)?;
+    crate::fs::create_private(key)?.write_all(generated.serialize_private_key_pem().as_bytes())?;
+
+    println!("Generated self-signed certificate at {:?}", cert);
+
+    Ok(())
+}
+
+pub fn load(cert: &Path, key: &Path, address: &str) -> io::Result<Arc<ServerConfig>> {
+    let (cert, key) = (resolve(cert), resolve(key));
+
+    if !cert.exists() && !key.exists() {
+        generate(&cert, &key, address)?;
+    }
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(&cert)?))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let private_key = rustls_pemfile::private_key(&mut BufReader::new(File::open(&key)?))?
+        .ok_or_else(|| invalid(format!("No private key found in {:?}", key)))?;
+
+    let Some(leaf) = certs.first() else {
+        return Err(invalid(format!("No certificate found in {:?}", cert)));
+    };
+
+    println!(
+        "TLS certificate fingerprint: {:x}",
+        Sha256::digest(leaf.as_ref())
+    );
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(invalid)?;
+
+    Ok(Arc::new(config))
+}